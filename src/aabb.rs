@@ -0,0 +1,105 @@
+use crate::{
+    interval::{Interval, INTERVAL_EMPTY},
+    mat4::Mat4,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+// axis aligned bounding box as one interval per axis
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        x: INTERVAL_EMPTY,
+        y: INTERVAL_EMPTY,
+        z: INTERVAL_EMPTY,
+    };
+
+    // treats a and b as opposite corners, in any order
+    pub fn from_points(a: Vec3<f32>, b: Vec3<f32>) -> Self {
+        let axis = |i: usize| Interval::new(a[i].min(b[i]), a[i].max(b[i]));
+        Self {
+            x: axis(0),
+            y: axis(1),
+            z: axis(2),
+        }
+    }
+
+    pub fn enclosing(a: &Aabb, b: &Aabb) -> Self {
+        Self {
+            x: Interval::enclosing(a.x, b.x),
+            y: Interval::enclosing(a.y, b.y),
+            z: Interval::enclosing(a.z, b.z),
+        }
+    }
+
+    // flat boxes break the slab test, give every side some thickness
+    pub fn padded(self) -> Self {
+        let delta = 1e-4;
+        let pad = |i: Interval| if i.size() < delta { i.expand(delta) } else { i };
+        Self {
+            x: pad(self.x),
+            y: pad(self.y),
+            z: pad(self.z),
+        }
+    }
+
+    // box around all eight transformed corners
+    pub fn transformed(&self, m: &Mat4) -> Self {
+        let (lo, hi) = (self.min(), self.max());
+        (0..8).fold(Aabb::EMPTY, |acc, i| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { lo.x() } else { hi.x() },
+                if i & 2 == 0 { lo.y() } else { hi.y() },
+                if i & 4 == 0 { lo.z() } else { hi.z() },
+            );
+            let p = m.transform_point(corner);
+            Aabb::enclosing(&acc, &Aabb::from_points(p, p))
+        })
+    }
+
+    pub fn axis_interval(&self, n: usize) -> Interval {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    pub fn min(&self) -> Vec3<f32> {
+        Vec3::new(self.x.min, self.y.min, self.z.min)
+    }
+
+    pub fn max(&self) -> Vec3<f32> {
+        Vec3::new(self.x.max, self.y.max, self.z.max)
+    }
+
+    // slab test, returns the part of ray_t inside the box
+    pub fn hit(&self, r: &Ray<f32>, ray_t: Interval) -> Option<Interval> {
+        let mut t = ray_t;
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / r.direction()[axis];
+            let t0 = (ax.min - r.origin()[axis]) * adinv;
+            let t1 = (ax.max - r.origin()[axis]) * adinv;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            t.min = t.min.max(t0);
+            t.max = t.max.min(t1);
+            if t.max <= t.min {
+                return None;
+            }
+        }
+        Some(t)
+    }
+}
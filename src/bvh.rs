@@ -0,0 +1,146 @@
+use crate::{
+    aabb::Aabb,
+    interval::Interval,
+    ray::Ray,
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+// bounding volume hierarchy over anything that can give a box per primitive. nodes sit in
+// one array in depth first order, so an interior node's first child directly follows it
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    // primitive indices, leaves point at a run of these
+    indices: Vec<usize>,
+}
+
+struct BvhNode {
+    bbox: Aabb,
+    // leaves: first entry in indices. interior nodes: index of the second child
+    offset: usize,
+    // primitives in a leaf, 0 for interior nodes
+    count: usize,
+    // split axis, decides which child to visit first
+    axis: usize,
+}
+
+const LEAF_SIZE: usize = 4;
+
+fn centroid(b: &Aabb) -> Vec3<f32> {
+    (b.min() + b.max()) * 0.5
+}
+
+impl Bvh {
+    pub fn new(boxes: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(boxes.len() * 2),
+            indices: (0..boxes.len()).collect(),
+        };
+        if !boxes.is_empty() {
+            bvh.build(boxes, 0, boxes.len());
+        }
+        bvh
+    }
+
+    // median split along the longest axis of the centroids
+    fn build(&mut self, boxes: &[Aabb], start: usize, end: usize) -> usize {
+        let node = self.nodes.len();
+        let bbox = self.indices[start..end]
+            .iter()
+            .fold(Aabb::EMPTY, |acc, &i| Aabb::enclosing(&acc, &boxes[i]));
+        self.nodes.push(BvhNode {
+            bbox,
+            offset: start,
+            count: end - start,
+            axis: 0,
+        });
+        if end - start <= LEAF_SIZE {
+            return node;
+        }
+
+        let centroids = self.indices[start..end]
+            .iter()
+            .fold(Aabb::EMPTY, |acc, &i| {
+                let c = centroid(&boxes[i]);
+                Aabb::enclosing(&acc, &Aabb::from_points(c, c))
+            });
+        let axis = (0..3)
+            .max_by(|&a, &b| {
+                let size = |n: usize| centroids.axis_interval(n).size();
+                size(a).total_cmp(&size(b))
+            })
+            .unwrap();
+        if centroids.axis_interval(axis).size() <= 0.0 {
+            // everything on top of each other, splitting won't help
+            return node;
+        }
+
+        let mid = start + (end - start) / 2;
+        self.indices[start..end].select_nth_unstable_by(mid - start, |&a, &b| {
+            centroid(&boxes[a])[axis].total_cmp(&centroid(&boxes[b])[axis])
+        });
+        self.build(boxes, start, mid);
+        let second = self.build(boxes, mid, end);
+        self.nodes[node] = BvhNode {
+            bbox,
+            offset: second,
+            count: 0,
+            axis,
+        };
+        node
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |n| n.bbox)
+    }
+
+    // calls hit_primitive for every primitive whose box the ray reaches before the closest
+    // hit so far. it gets the primitive index and the interval still worth testing and
+    // returns the t of its hit, if any. returns the closest t found
+    pub fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        mut hit_primitive: impl FnMut(usize, Interval) -> Option<f32>,
+    ) -> Option<f32> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut closest = ray_t.max;
+        let mut found = None;
+        let mut stack = Vec::with_capacity(64);
+        // counted locally, the thread local counters are touched once per ray
+        let (mut visits, mut tests) = (0, 0);
+        stack.push(0);
+        while let Some(index) = stack.pop() {
+            visits += 1;
+            let node = &self.nodes[index];
+            if node
+                .bbox
+                .hit(r, Interval::new(ray_t.min, closest))
+                .is_none()
+            {
+                continue;
+            }
+            if node.count > 0 {
+                tests += node.count as u64;
+                for &prim in &self.indices[node.offset..node.offset + node.count] {
+                    if let Some(t) = hit_primitive(prim, Interval::new(ray_t.min, closest)) {
+                        closest = t;
+                        found = Some(t);
+                    }
+                }
+            } else if r.direction()[node.axis] < 0.0 {
+                // the second child is nearer, pop it first
+                stack.push(index + 1);
+                stack.push(node.offset);
+            } else {
+                stack.push(node.offset);
+                stack.push(index + 1);
+            }
+        }
+        stats::count(Counter::BvhNodeVisits, visits);
+        stats::count(Counter::IntersectionTests, tests);
+        found
+    }
+}
@@ -1,19 +1,93 @@
+use std::{
+    cell::RefCell,
+    io::{Error, ErrorKind, Result},
+    time::{Duration, Instant},
+};
+
 use crate::{
-    random_double,
-    vec3::{random_on_hemisphere, random_unit_vec, Vec3},
-    write_color, HitRecord, Hittable, HittableList, Interval, Ppm, Ray,
+    checkpoint::RenderState,
+    image::{Framebuffer, RenderMetadata},
+    light::power_heuristic,
+    material::Material,
+    random_double, rng,
+    scene::Scene,
+    stats::{self, Counter, RenderStats},
+    tile::{tiles, Rect, TileOrder},
+    vec3::Vec3,
+    HitRecord, Interval, Ray,
 };
 
+/// When a progressive render stops, how often it shows what it has and where it keeps
+/// checkpoints.
+#[derive(Debug, Clone)]
+pub struct Progressive {
+    /// Samples added to every pixel per pass.
+    pub samples_per_pass: usize,
+    /// Samples per pixel to stop at.
+    pub target_samples: usize,
+    /// Stops before a pass that would likely run past this.
+    pub time_budget: Option<Duration>,
+    /// Passes between previews, 0 for none.
+    pub preview_interval: usize,
+    /// File the [`RenderState`] is saved to, see [`Camera::resume`].
+    pub checkpoint: Option<String>,
+    /// Least time between two checkpoints. One is always written when the render stops.
+    pub checkpoint_interval: Duration,
+}
+
+impl Default for Progressive {
+    fn default() -> Self {
+        Self {
+            samples_per_pass: 4,
+            target_samples: 100,
+            time_budget: None,
+            preview_interval: 1,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// How far a render has got, see [`Camera::set_progress`].
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Part of the render done, from 0 to 1. `None` when it runs until stopped.
+    pub fraction: Option<f32>,
+    /// Samples taken in this session.
+    pub samples: u64,
+    /// Time since this session started.
+    pub elapsed: Duration,
+    pub rays_per_second: f64,
+    /// Estimated time left, once there is something to go by.
+    pub eta: Option<Duration>,
+}
+
+type ProgressFn = Box<dyn FnMut(&Progress)>;
+
+/// Pinhole camera that path traces a [`Scene`] into a [`Framebuffer`].
+///
+/// Starts out at the origin looking down -z with a 90 degree vertical field of view,
+/// rendering 400 by 225 pixels at 100 samples each.
 pub struct Camera {
     image_width: f32,
     image_height: f32,
+    look_from: Vec3<f32>,
+    look_at: Vec3<f32>,
+    vup: Vec3<f32>,
+    vfov: f32,
     center: Vec3<f32>,
     pixel00_loc: Vec3<f32>,
     pixel_delta_u: Vec3<f32>,
     pixel_delta_v: Vec3<f32>,
     samples_per_pixel: f32,
-    pixel_samples_scale: f32,
     max_depth: usize,
+    shutter_open: f32,
+    shutter_close: f32,
+    seed: u64,
+    tile_size: usize,
+    tile_order: TileOrder,
+    crop: Option<Rect>,
+    progress: Option<RefCell<ProgressFn>>,
 }
 
 macro_rules! f32_len {
@@ -32,6 +106,7 @@ macro_rules! unit_v {
 }
 
 impl Camera {
+    /// The default camera described above.
     pub fn initialize() -> Self {
         let aspect_ratio = 16.0 / 9.0;
         let image_width = 400.0;
@@ -39,40 +114,142 @@ impl Camera {
         if image_height < 1.0 {
             image_height = 1.0;
         }
-        // Camera
+        let samples_per_pixel = 100.0;
+        let max_depth = 50;
+        let mut camera = Self {
+            image_width,
+            image_height,
+            look_from: Vec3::new(0.0, 0.0, 0.0),
+            look_at: Vec3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            center: Vec3::new(0.0, 0.0, 0.0),
+            pixel00_loc: Vec3::new(0.0, 0.0, 0.0),
+            pixel_delta_u: Vec3::new(0.0, 0.0, 0.0),
+            pixel_delta_v: Vec3::new(0.0, 0.0, 0.0),
+            samples_per_pixel,
+            max_depth,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            seed: 0,
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            crop: None,
+            progress: None,
+        };
+        camera.update();
+        camera
+    }
+
+    /// Places the camera at `from` looking towards `at`, `vfov` is the vertical field of
+    /// view in degrees. The image size stays the same.
+    pub fn look_at(&mut self, from: Vec3<f32>, at: Vec3<f32>, up: Vec3<f32>, vfov: f32) {
+        self.look_from = from;
+        self.look_at = at;
+        self.vup = up;
+        self.vfov = vfov;
+        self.update();
+    }
+
+    /// Output resolution in pixels, the field of view stays vertical.
+    pub fn set_image_size(&mut self, width: usize, height: usize) {
+        self.image_width = width.max(1) as f32;
+        self.image_height = height.max(1) as f32;
+        self.update();
+    }
+
+    pub fn set_samples_per_pixel(&mut self, samples: usize) {
+        self.samples_per_pixel = samples.max(1) as f32;
+    }
+
+    pub fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel as usize
+    }
+
+    /// Bounces before a path is cut off.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Picks the random numbers of every sample, the same seed renders the same image.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Renders in tiles of `size` by `size` pixels, taken in `order`.
+    pub fn set_tiles(&mut self, size: usize, order: TileOrder) {
+        self.tile_size = size.max(1);
+        self.tile_order = order;
+    }
+
+    /// Only renders the pixels in `crop`, clipped to the image. The framebuffer then
+    /// covers just that window, the view stays that of the whole image so the window can
+    /// be pasted back into a full render. `None` renders everything again.
+    pub fn set_crop(&mut self, crop: Option<Rect>) {
+        self.crop = crop;
+    }
+
+    /// Calls `progress` every time a tile is finished, in any kind of render.
+    pub fn set_progress(&mut self, progress: impl FnMut(&Progress) + 'static) {
+        self.progress = Some(RefCell::new(Box::new(progress)));
+    }
+
+    pub(crate) fn image_size(&self) -> (usize, usize) {
+        (self.image_width as usize, self.image_height as usize)
+    }
+
+    pub(crate) fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    // the pixels actually rendered, the crop window or the whole image
+    fn window(&self) -> Rect {
+        let (width, height) = self.image_size();
+        let full = Rect::new(0, 0, width, height);
+        self.crop.map_or(full, |crop| crop.intersect(&full))
+    }
+
+    // fewest samples in any pixel being rendered
+    fn window_samples(&self, state: &RenderState) -> usize {
+        self.window()
+            .pixels()
+            .map(|(x, y)| state.samples[y * state.width + x] as usize)
+            .min()
+            .unwrap_or(usize::MAX)
+    }
+
+    // recomputes the viewport from the view and the image size
+    fn update(&mut self) {
+        let (from, at, up) = (self.look_from, self.look_at, self.vup);
         // viewport height and width may not match the aspect ratio
         let focal_length = 1.0;
-        let viewport_height = 2.0;
-        let viewport_width = viewport_height * image_width / image_height;
+        let viewport_height = 2.0 * (self.vfov.to_radians() * 0.5).tan() * focal_length;
+        let viewport_width = viewport_height * self.image_width / self.image_height;
 
-        let center = Vec3::<f32>::new(0.0, 0.0, 0.0);
+        // orthonormal basis, w points backwards out of the screen
+        let unit = |a: Vec3<f32>| a / a.length_squared().sqrt();
+        let w = unit(from - at);
+        let u = unit(up.cross(w));
+        let v = w.cross(u);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges.
-        let viewport_u = Vec3::<f32>::new(viewport_width, 0.0, 0.0);
-        let viewport_v = Vec3::<f32>::new(0.0, -viewport_height, 0.0);
+        let viewport_u = u * viewport_width;
+        let viewport_v = -v * viewport_height;
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel.
-        let pixel_delta_u = viewport_u / image_width;
-        let pixel_delta_v = viewport_v / image_height;
+        self.pixel_delta_u = viewport_u / self.image_width;
+        self.pixel_delta_v = viewport_v / self.image_height;
 
         // Calculate the location of the upper left pixel.
-        let viewport_upper_left =
-            center - Vec3::<f32>::new(0.0, 0.0, focal_length) - viewport_u / 2.0 - viewport_v / 2.0;
-        let pixel00_loc = viewport_upper_left + (pixel_delta_u + pixel_delta_v) * 0.5;
-        let samples_per_pixel = 100.0;
-        let pixel_samples_scale = 1.0 / samples_per_pixel;
-        let max_depth = 50;
-        Self {
-            image_width,
-            image_height,
-            center,
-            pixel00_loc,
-            pixel_delta_u,
-            pixel_delta_v,
-            samples_per_pixel,
-            pixel_samples_scale,
-            max_depth,
-        }
+        let viewport_upper_left = from - w * focal_length - viewport_u / 2.0 - viewport_v / 2.0;
+        self.pixel00_loc = viewport_upper_left + (self.pixel_delta_u + self.pixel_delta_v) * 0.5;
+        self.center = from;
+    }
+
+    /// Rays are spread uniformly over `[open, close]`, objects move in the same time units.
+    pub fn set_shutter(&mut self, open: f32, close: f32) {
+        self.shutter_open = open;
+        self.shutter_close = close;
     }
 
     fn sample_square() -> Vec3<f32> {
@@ -85,66 +262,385 @@ impl Camera {
             + (self.pixel_delta_u * (width + offset.x()))
             + (self.pixel_delta_v * (height + offset.y()));
         let ray_direction = pixel_sample - self.center;
-        Ray {
-            origin: self.center,
-            direction: ray_direction,
+        let ray_time =
+            self.shutter_open + random_double() * (self.shutter_close - self.shutter_open);
+        Ray::with_time(self.center, ray_direction, ray_time)
+    }
+
+    /// Renders the scene into a linear [`Framebuffer`], nothing is written anywhere.
+    pub fn render(&self, scene: &Scene) -> Framebuffer {
+        let mut state = self.render_state();
+        let spp = self.samples_per_pixel as usize;
+        let mut session = Session::new(self, &state, spp, None);
+        self.render_pass(scene, &mut state, spp, spp, &mut session);
+        session.end_pass();
+        state.elapsed = session.stats.wall_time;
+        self.framebuffer(&state, &session.stats)
+    }
+
+    /// An empty [`RenderState`] matching this camera, for [`Camera::resume`].
+    pub fn render_state(&self) -> RenderState {
+        let (width, height) = self.image_size();
+        RenderState::new(width, height, self.max_depth, self.seed)
+    }
+
+    /// Renders in passes of `settings.samples_per_pass` samples per pixel, accumulating
+    /// until the target sample count or the time budget is reached. `preview` sees the
+    /// image so far every `settings.preview_interval` passes.
+    ///
+    /// Fails only when a checkpoint can't be written.
+    pub fn render_progressive(
+        &self,
+        scene: &Scene,
+        settings: &Progressive,
+        preview: impl FnMut(&Framebuffer),
+    ) -> Result<Framebuffer> {
+        self.resume(scene, self.render_state(), settings, preview)
+    }
+
+    /// Like [`Camera::render_progressive`] but carries on from `state`, usually loaded
+    /// from a checkpoint. Uses the seed stored in the state, so the result is the same as
+    /// if the render had never stopped. The time budget counts this session only.
+    ///
+    /// Fails if the state was rendered at another size or depth.
+    pub fn resume(
+        &self,
+        scene: &Scene,
+        state: RenderState,
+        settings: &Progressive,
+        preview: impl FnMut(&Framebuffer),
+    ) -> Result<Framebuffer> {
+        self.accumulate(
+            state,
+            settings,
+            preview,
+            |state, target, per_pass, session| {
+                self.render_pass(scene, state, target, per_pass, session);
+                Ok(())
+            },
+        )
+    }
+
+    // the progressive loop around pass, which tops the window up by per_pass samples
+    // without going past target, wherever the samples come from
+    pub(crate) fn accumulate(
+        &self,
+        mut state: RenderState,
+        settings: &Progressive,
+        mut preview: impl FnMut(&Framebuffer),
+        mut pass: impl FnMut(&mut RenderState, usize, usize, &mut Session) -> Result<()>,
+    ) -> Result<Framebuffer> {
+        let size = self.image_size();
+        if (state.width, state.height) != size || state.max_depth != self.max_depth {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "render state is {}x{} at depth {}, the camera renders {}x{} at depth {}",
+                    state.width, state.height, state.max_depth, size.0, size.1, self.max_depth
+                ),
+            ));
+        }
+
+        let mut session = Session::new(self, &state, settings.target_samples, settings.time_budget);
+        let resumed = state.elapsed;
+        let mut last_checkpoint = session.start;
+        let per_pass = settings.samples_per_pass.max(1);
+        let mut passes: usize = 0;
+        while self.window_samples(&state) < settings.target_samples {
+            pass(&mut state, settings.target_samples, per_pass, &mut session)?;
+            passes += 1;
+
+            session.end_pass();
+            let elapsed = session.stats.wall_time;
+            state.elapsed = resumed + elapsed;
+            let done = self.window_samples(&state) >= settings.target_samples
+                || settings.time_budget.is_some_and(|budget| {
+                    // stop early rather than run a pass past the budget
+                    elapsed + elapsed / passes as u32 > budget
+                });
+            if done {
+                break;
+            }
+            if let Some(path) = &settings.checkpoint {
+                if last_checkpoint.elapsed() >= settings.checkpoint_interval {
+                    state.save(path)?;
+                    last_checkpoint = Instant::now();
+                }
+            }
+            if settings.preview_interval > 0 && passes.is_multiple_of(settings.preview_interval) {
+                preview(&self.framebuffer(&state, &session.stats));
+            }
+        }
+        if let Some(path) = &settings.checkpoint {
+            state.save(path)?;
+        }
+        Ok(self.framebuffer(&state, &session.stats))
+    }
+
+    // the window split up the way it is rendered
+    pub(crate) fn tiles(&self) -> Vec<Rect> {
+        tiles(self.window(), self.tile_size, self.tile_order)
+    }
+
+    fn render_pass(
+        &self,
+        scene: &Scene,
+        state: &mut RenderState,
+        target: usize,
+        per_pass: usize,
+        session: &mut Session,
+    ) {
+        for tile in self.tiles() {
+            let mut added = 0;
+            for (x, y) in tile.pixels() {
+                let i = y * state.width + x;
+                let taken = &mut state.samples[i];
+                let n = per_pass.min(target.saturating_sub(*taken as usize)) as u32;
+                self.render_pixel(scene, state.seed, (x, y), &mut state.sum[i], taken, n);
+                added += n as u64;
+            }
+            session.tile_done(added);
         }
     }
 
-    pub fn render(&self, world: &mut HittableList) {
-        let pixels: Vec<(f32, f32, f32)> = (0..self.image_height as usize
-            * self.image_width as usize)
-            .map(|i| {
-                let height = ((i as f32) / self.image_width) as u32 as f32;
-                let width = ((i as f32) % self.image_width) as u32 as f32;
-                //let r = Ray::new(self.center, ray_direction);
-                let mut color = Vec3::new(0.0, 0.0, 0.0);
-                for _ in 0..self.samples_per_pixel as usize {
-                    let r = self.get_ray(width, height);
-                    color += Self::ray_color(&r, self.max_depth, world);
+    // adds n samples to a pixel's sum, continuing after the taken ones. a sample's random
+    // numbers depend on the pixel, not on the tiling or who renders it
+    pub(crate) fn render_pixel(
+        &self,
+        scene: &Scene,
+        seed: u64,
+        (x, y): (usize, usize),
+        sum: &mut Vec3<f32>,
+        taken: &mut u32,
+        n: u32,
+    ) {
+        let i = y * self.image_width as usize + x;
+        stats::count(Counter::PrimaryRays, n as u64);
+        for sample in *taken..*taken + n {
+            rng::seed_sample(seed, i, sample);
+            let r = self.get_ray(x as f32, y as f32);
+            *sum += Self::ray_color(&r, self.max_depth, scene);
+        }
+        *taken += n;
+    }
+
+    fn framebuffer(&self, state: &RenderState, stats: &RenderStats) -> Framebuffer {
+        let window = self.window();
+        let samples = if window.area() > 0 {
+            self.window_samples(state)
+        } else {
+            0
+        };
+        Framebuffer {
+            width: window.width,
+            height: window.height,
+            pixels: window
+                .pixels()
+                .map(|(x, y)| {
+                    let i = y * state.width + x;
+                    state.sum[i] * (1.0 / state.samples[i].max(1) as f32)
+                })
+                .collect(),
+            metadata: RenderMetadata {
+                samples_per_pixel: samples,
+                max_depth: self.max_depth,
+                shutter: (self.shutter_open, self.shutter_close),
+                render_time: state.elapsed,
+                crop: self.crop.map(|_| window),
+                stats: stats.clone(),
+            },
+        }
+    }
+
+    fn ray_color(r: &Ray<f32>, max_depth: usize, scene: &Scene) -> Vec3<f32> {
+        let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        let mut ray = *r;
+        // camera rays and specular bounces can't be matched by light sampling
+        let mut specular_bounce = true;
+        let mut bsdf_pdf = 0.0;
+        // surfaces hit so far
+        let mut length = 0;
+
+        for bounce in 0..max_depth {
+            if bounce > 0 {
+                stats::count(Counter::SecondaryRays, 1);
+            }
+            let mut rec = HitRecord::default();
+            if !scene.world.hit(
+                &ray,
+                Interval {
+                    min: 0.025,
+                    max: f32::INFINITY,
+                },
+                &mut rec,
+            ) {
+                radiance += throughput * scene.background.value(ray.direction());
+                let le = scene.infinite_light(ray.direction());
+                if le.length_squared() > 0.0 {
+                    let weight = if specular_bounce {
+                        1.0
+                    } else {
+                        power_heuristic(bsdf_pdf, scene.light_pdf(ray.origin(), ray.direction()))
+                    };
+                    radiance += throughput * le * weight;
                 }
-                color = color * self.pixel_samples_scale;
-                (color.x(), color.y(), color.z())
-            })
-            .collect();
-        let ppm_writer = Ppm::new(
-            self.image_width as usize,
-            self.image_height as usize,
-            255,
-            &pixels
-                .into_iter()
-                .flat_map(write_color)
-                .collect::<Vec<u8>>(),
-        );
-        ppm_writer.write("out.ppm").unwrap();
+                break;
+            }
+            length += 1;
+            let Some(mat) = rec.material.clone() else {
+                break;
+            };
+            let wo = -unit_v!(ray.direction());
+
+            let emitted = mat.emitted(&rec);
+            if emitted.length_squared() > 0.0 {
+                let weight = if specular_bounce {
+                    1.0
+                } else {
+                    power_heuristic(bsdf_pdf, scene.light_pdf(ray.origin(), -wo))
+                };
+                radiance += throughput * emitted * weight;
+            }
+
+            let Some(sample) = mat.sample(&ray, &rec) else {
+                break;
+            };
+            if !sample.flags.is_specular() {
+                radiance +=
+                    throughput * Self::sample_direct(scene, &ray, &rec, mat.as_ref().as_ref(), wo);
+            }
+
+            throughput *= sample.weight(mat.cos_factor(&rec, sample.wi));
+            specular_bounce = sample.flags.is_specular();
+            bsdf_pdf = sample.pdf;
+            ray = Ray::with_time(rec.p, sample.wi, ray.time());
+        }
+        stats::record_path(length);
+        radiance
     }
 
-    fn ray_color(r: &Ray<f32>, depth: usize, world: &mut HittableList) -> Vec3<f32> {
-        if depth == 0 {
-            return Vec3::new(0.0, 0.0, 0.0);
+    // one light sample with a shadow ray, weighted against bsdf sampling
+    fn sample_direct(
+        scene: &Scene,
+        ray: &Ray<f32>,
+        rec: &HitRecord,
+        mat: &dyn Material,
+        wo: Vec3<f32>,
+    ) -> Vec3<f32> {
+        let zero = Vec3::new(0.0, 0.0, 0.0);
+        let Some((light, pick_pdf)) = scene.pick_light() else {
+            return zero;
+        };
+        let Some(ls) = light.sample_li(rec.p) else {
+            return zero;
+        };
+        let f = mat.eval(rec, wo, ls.wi);
+        if ls.pdf <= 0.0 || f.length_squared() == 0.0 {
+            return zero;
         }
-        let mut rec = HitRecord::default();
-        if world.hit(
-            r,
+
+        stats::count(Counter::ShadowRays, 1);
+        let transmittance = scene.world.transmittance(
+            &Ray::with_time(rec.p, ls.wi, ray.time()),
             Interval {
                 min: 0.025,
-                max: f32::INFINITY,
+                max: ls.distance - 0.025,
             },
-            &mut rec,
-        ) {
-            let mut scattered = Ray::default();
-            let mut attenuation = Vec3::<f32>::default();
-            if let Some(mat) = &rec.material {
-                if mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-                    return attenuation * Self::ray_color(&scattered, depth - 1, world);
-                }
-            }
-            return Vec3::new(0.0, 0.0, 0.0);
+        );
+        if transmittance == 0.0 {
+            return zero;
         }
 
-        let unit_direction = unit_v!(r.direction());
-        let a = (unit_direction.y() + 1.0) * 0.5;
+        // weighted with the density over all lights like the bsdf side in ray_color, so
+        // the weights of both still add up to one where lights overlap
+        let weight = if light.is_delta() {
+            1.0
+        } else {
+            power_heuristic(scene.light_pdf(rec.p, ls.wi), mat.pdf(rec, wo, ls.wi))
+        };
+        f * ls.li * (mat.cos_factor(rec, ls.wi) * transmittance * weight / (ls.pdf * pick_pdf))
+    }
+}
 
-        Vec3::new(1.0, 1.0, 1.0) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.0) * a
+// one render session: the work done so far and how far there is to go, reported to the
+// progress callback
+pub(crate) struct Session<'a> {
+    camera: &'a Camera,
+    start: Instant,
+    // samples missing at the start, None without a sample target
+    needed: Option<u64>,
+    budget: Option<Duration>,
+    samples: u64,
+    pub(crate) stats: RenderStats,
+}
+
+impl<'a> Session<'a> {
+    fn new(
+        camera: &'a Camera,
+        state: &RenderState,
+        target: usize,
+        budget: Option<Duration>,
+    ) -> Self {
+        // whatever ran on this thread before isn't part of the render
+        stats::take();
+        let needed = (target != usize::MAX).then(|| {
+            camera
+                .window()
+                .pixels()
+                .map(|(x, y)| target.saturating_sub(state.samples[y * state.width + x] as usize))
+                .sum::<usize>() as u64
+        });
+        Self {
+            camera,
+            start: Instant::now(),
+            needed,
+            budget,
+            samples: 0,
+            stats: RenderStats::default(),
+        }
+    }
+
+    pub(crate) fn tile_done(&mut self, samples: u64) {
+        self.samples += samples;
+        if let Some(progress) = &self.camera.progress {
+            (progress.borrow_mut())(&self.progress());
+        }
+    }
+
+    fn end_pass(&mut self) {
+        self.stats.merge(&stats::take());
+        self.stats.wall_time = self.start.elapsed();
+    }
+
+    fn progress(&self) -> Progress {
+        let elapsed = self.start.elapsed();
+        let by_samples = self.needed.map(|n| {
+            if n == 0 {
+                1.0
+            } else {
+                self.samples as f32 / n as f32
+            }
+        });
+        let by_time = self
+            .budget
+            .map(|b| elapsed.as_secs_f32() / b.as_secs_f32().max(1e-6));
+        // whichever stops the render first
+        let fraction = match (by_samples, by_time) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+        .map(|f| f.min(1.0));
+        let eta = fraction
+            .filter(|&f| f > 0.0)
+            .map(|f| elapsed.mul_f32((1.0 - f) / f));
+        let rays = self.stats.rays() + stats::pending_rays();
+        Progress {
+            fraction,
+            samples: self.samples,
+            elapsed,
+            rays_per_second: rays as f64 / elapsed.as_secs_f64().max(1e-9),
+            eta,
+        }
     }
 }
@@ -0,0 +1,200 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write},
+    time::Duration,
+};
+
+use crate::vec3::Vec3;
+
+/// Accumulated samples of a render in progress, enough to pick it up again later.
+///
+/// Every sample draws its random numbers from a sequence picked by `seed`, the pixel and
+/// the sample's index, so the seed is all the generator state there is to keep. Resuming
+/// adds the same samples an uninterrupted render would have and gives the same image.
+#[derive(Debug, Clone)]
+pub struct RenderState {
+    pub width: usize,
+    pub height: usize,
+    pub max_depth: usize,
+    pub seed: u64,
+    /// Sum of the samples of each pixel, rows top to bottom.
+    pub sum: Vec<Vec3<f32>>,
+    /// Samples taken in each pixel.
+    pub samples: Vec<u32>,
+    /// Render time spent so far, over all sessions.
+    pub elapsed: Duration,
+}
+
+const MAGIC: &[u8; 8] = b"RTCKPT01";
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(r: &mut impl Read) -> Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_f32(r: &mut impl Read) -> Result<f32> {
+    Ok(f32::from_bits(read_u32(r)?))
+}
+
+impl RenderState {
+    /// An empty image, no samples taken yet.
+    pub fn new(width: usize, height: usize, max_depth: usize, seed: u64) -> Self {
+        Self {
+            width,
+            height,
+            max_depth,
+            seed,
+            sum: vec![Vec3::new(0.0, 0.0, 0.0); width * height],
+            samples: vec![0; width * height],
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Fewest samples taken in any pixel.
+    pub fn min_samples(&self) -> u32 {
+        self.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    /// Writes the state to `path` in a small little endian binary format. The file is
+    /// written next to it first and renamed over it, so a crash mid-write keeps the
+    /// previous checkpoint intact.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let tmp = format!("{path}.tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        for v in [self.width, self.height, self.max_depth] {
+            out.write_all(&(v as u32).to_le_bytes())?;
+        }
+        out.write_all(&self.seed.to_le_bytes())?;
+        out.write_all(&(self.elapsed.as_nanos() as u64).to_le_bytes())?;
+        for (c, n) in self.sum.iter().zip(&self.samples) {
+            out.write_all(&n.to_le_bytes())?;
+            for v in [c.x(), c.y(), c.z()] {
+                out.write_all(&v.to_le_bytes())?;
+            }
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Reads a checkpoint written by [`RenderState::save`].
+    pub fn load(path: &str) -> Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a render checkpoint"));
+        }
+        let width = read_u32(&mut r)? as usize;
+        let height = read_u32(&mut r)? as usize;
+        let max_depth = read_u32(&mut r)? as usize;
+        let seed = read_u64(&mut r)?;
+        let elapsed = Duration::from_nanos(read_u64(&mut r)?);
+        let count = width
+            .checked_mul(height)
+            .ok_or_else(|| invalid("checkpoint image too large"))?;
+
+        // a truncated file fails the reads before a bogus size can allocate much
+        let mut sum = Vec::new();
+        let mut samples = Vec::new();
+        for _ in 0..count {
+            samples.push(read_u32(&mut r)?);
+            sum.push(Vec3::new(
+                read_f32(&mut r)?,
+                read_f32(&mut r)?,
+                read_f32(&mut r)?,
+            ));
+        }
+        if r.read(&mut [0])? != 0 {
+            return Err(invalid("trailing data in checkpoint"));
+        }
+        Ok(Self {
+            width,
+            height,
+            max_depth,
+            seed,
+            sum,
+            samples,
+            elapsed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, rc::Rc};
+
+    use super::*;
+    use crate::{material::Lambertian, scene::Scene, Camera, HittableList, Progressive, Sphere};
+
+    fn scene() -> Scene {
+        let mut world = HittableList::default();
+        world.add(Box::new(Sphere {
+            center: Vec3::new(0.0, 0.0, -1.0),
+            radius: 0.5,
+            material: Rc::new(Box::new(Lambertian {
+                albedo: Vec3::new(0.5, 0.5, 0.5),
+            })),
+        }));
+        let mut scene = Scene::new(world);
+        scene.add_sphere_light(Vec3::new(0.0, 2.0, 0.0), 0.5, Vec3::new(4.0, 4.0, 4.0));
+        scene
+    }
+
+    // renders up to target samples per pixel, keeping the state in checkpoint
+    fn render(camera: &Camera, state: RenderState, target: usize, checkpoint: &str) {
+        let settings = Progressive {
+            samples_per_pass: 2,
+            target_samples: target,
+            preview_interval: 0,
+            checkpoint: Some(checkpoint.to_string()),
+            ..Progressive::default()
+        };
+        camera.resume(&scene(), state, &settings, |_| {}).unwrap();
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        let mut camera = Camera::initialize();
+        camera.set_image_size(12, 8);
+        camera.set_max_depth(4);
+        camera.set_seed(7);
+        camera.set_tiles(5, Default::default());
+        let dir = env::temp_dir();
+        let straight = dir.join(format!("rt-straight-{}.ckpt", std::process::id()));
+        let split = dir.join(format!("rt-split-{}.ckpt", std::process::id()));
+        let (straight, split) = (straight.to_str().unwrap(), split.to_str().unwrap());
+
+        render(&camera, camera.render_state(), 5, straight);
+        // stop partway, odd so the last pass of the first session is a partial one
+        render(&camera, camera.render_state(), 3, split);
+        render(&camera, RenderState::load(split).unwrap(), 5, split);
+
+        let (a, b) = (
+            RenderState::load(straight).unwrap(),
+            RenderState::load(split).unwrap(),
+        );
+        let _ = (fs::remove_file(straight), fs::remove_file(split));
+        assert_eq!(a.samples, b.samples);
+        assert!(a.samples.iter().all(|&n| n == 5));
+        assert!(a.sum.iter().any(|c| c.x() > 0.0));
+        let bits = |s: &RenderState| -> Vec<[u32; 3]> {
+            s.sum
+                .iter()
+                .map(|c| [c.x(), c.y(), c.z()].map(f32::to_bits))
+                .collect()
+        };
+        assert_eq!(bits(&a), bits(&b));
+    }
+}
@@ -0,0 +1,211 @@
+use std::rc::Rc;
+
+use crate::{aabb::Aabb, interval::Interval, material::Material, ray::Ray, HitRecord, Hittable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // a with b carved out of it
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// boolean combination of two closed objects. both are walked along the ray at once and
+// a crossing of either child is kept when it changes whether the ray is inside the result.
+// hit normals already face the ray, so a kept crossing only has its front_face updated,
+// which is what flips the inner walls of a difference
+pub struct Csg {
+    pub op: CsgOp,
+    pub a: Box<dyn Hittable>,
+    pub b: Box<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Self {
+        let (box_a, box_b) = (a.bounding_box(), b.bounding_box());
+        let bbox = match op {
+            CsgOp::Union => Aabb::enclosing(&box_a, &box_b),
+            CsgOp::Intersection => Aabb {
+                x: Interval::intersect(box_a.x, box_b.x),
+                y: Interval::intersect(box_a.y, box_b.y),
+                z: Interval::intersect(box_a.z, box_b.z),
+            },
+            CsgOp::Difference => box_a,
+        };
+        Self { op, a, b, bbox }
+    }
+
+    pub fn union(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Difference, a, b)
+    }
+}
+
+// a ray that starts inside an object meets a leaving crossing first
+fn starts_inside(hits: &[HitRecord]) -> bool {
+    hits.first().is_some_and(|h| !h.front_face)
+}
+
+impl Hittable for Csg {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let mut hits = Vec::new();
+        self.hit_all(r, &mut hits);
+        match hits.into_iter().find(|h| ray_t.surrounds(h.t)) {
+            Some(h) => {
+                *rec = h;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.a.material()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_all(&self, r: &Ray<f32>, hits: &mut Vec<HitRecord>) {
+        let (mut hits_a, mut hits_b) = (Vec::new(), Vec::new());
+        self.a.hit_all(r, &mut hits_a);
+        self.b.hit_all(r, &mut hits_b);
+        let mut in_a = starts_inside(&hits_a);
+        let mut in_b = starts_inside(&hits_b);
+        let mut inside = self.op.inside(in_a, in_b);
+
+        let (mut ia, mut ib) = (hits_a.into_iter().peekable(), hits_b.into_iter().peekable());
+        loop {
+            let from_a = match (ia.peek(), ib.peek()) {
+                (Some(a), Some(b)) => a.t <= b.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let mut h = if from_a {
+                let h = ia.next().unwrap();
+                in_a = h.front_face;
+                h
+            } else {
+                let h = ib.next().unwrap();
+                in_b = h.front_face;
+                h
+            };
+            let now_inside = self.op.inside(in_a, in_b);
+            if now_inside != inside {
+                inside = now_inside;
+                h.front_face = now_inside;
+                hits.push(h);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, vec3::Vec3, Sphere};
+
+    fn sphere(x: f32, radius: f32) -> Box<dyn Hittable> {
+        Box::new(Sphere {
+            center: Vec3::new(x, 0.0, 0.0),
+            radius,
+            material: Rc::new(Box::new(Lambertian {
+                albedo: Vec3::new(0.5, 0.5, 0.5),
+            })),
+        })
+    }
+
+    // unit spheres overlapping over x in [-0.5, 0.5]
+    fn pair(op: CsgOp) -> Csg {
+        Csg::new(op, sphere(-0.5, 1.0), sphere(0.5, 1.0))
+    }
+
+    // crossings along the x axis as (x, entering)
+    fn crossings(object: &dyn Hittable) -> Vec<(f32, bool)> {
+        let r = Ray::new(Vec3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut hits = Vec::new();
+        object.hit_all(&r, &mut hits);
+        hits.iter()
+            .map(|h| {
+                // normals keep facing the ray
+                assert!((h.normal.x() + 1.0).abs() < 1e-3);
+                // to the nearest half, every crossing here is on one
+                (((h.t - 10.0) * 2.0).round() / 2.0, h.front_face)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn interval_sets() {
+        assert_eq!(crossings(&pair(CsgOp::Union)), [(-1.5, true), (1.5, false)]);
+        // the lens
+        assert_eq!(
+            crossings(&pair(CsgOp::Intersection)),
+            [(-0.5, true), (0.5, false)]
+        );
+        // leaves through the wall of the carved out sphere
+        assert_eq!(
+            crossings(&pair(CsgOp::Difference)),
+            [(-1.5, true), (-0.5, false)]
+        );
+        let reversed = Csg::difference(sphere(0.5, 1.0), sphere(-0.5, 1.0));
+        assert_eq!(crossings(&reversed), [(0.5, true), (1.5, false)]);
+    }
+
+    #[test]
+    fn nested_nodes_and_misses() {
+        let hollow = Csg::difference(Box::new(pair(CsgOp::Union)), sphere(0.0, 0.5));
+        assert_eq!(
+            crossings(&hollow),
+            [(-1.5, true), (-0.5, false), (0.5, true), (1.5, false)]
+        );
+        let apart = Csg::intersection(sphere(-2.0, 1.0), sphere(2.0, 1.0));
+        assert!(crossings(&apart).is_empty());
+    }
+
+    #[test]
+    fn hit_starts_inside_the_lens() {
+        let lens = pair(CsgOp::Intersection);
+        let r = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(lens.hit(
+            &r,
+            Interval::new(0.001, f32::INFINITY),
+            &mut rec,
+            lens.material()
+        ));
+        assert!((rec.t - 0.5).abs() < 1e-4 && !rec.front_face);
+        assert!(!lens.hit(
+            &r,
+            Interval::new(0.6, f32::INFINITY),
+            &mut rec,
+            lens.material()
+        ));
+    }
+}
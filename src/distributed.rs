@@ -0,0 +1,565 @@
+use std::{
+    io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write},
+    net::TcpStream,
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    camera::Session,
+    checkpoint::RenderState,
+    image::Framebuffer,
+    scene::Scene,
+    stats::{self, RenderStats},
+    tile::Rect,
+    vec3::Vec3,
+    Camera, Progressive,
+};
+
+// messages start with a tag byte, numbers are little endian. the coordinator opens with
+// HELLO and then sends JOBs, the worker answers each with the same tag or with ERROR.
+// finished tiles come back with the worker's stats for them
+const HELLO: u8 = 0;
+const JOB: u8 = 1;
+const ERROR: u8 = 2;
+const VERSION: u32 = 2;
+
+/// How long a worker may take to answer before it counts as failed, and how long
+/// [`serve`] waits on a coordinator that stops in the middle of a message.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u8(r: &mut impl Read) -> Result<u8> {
+    let mut b = [0; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(r: &mut impl Read) -> Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn write_u32(w: &mut impl Write, v: u32) -> Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_u64(w: &mut impl Write, v: u64) -> Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+// the sums and sample counts of one tile, travelling to a worker and back
+struct TileData {
+    rect: Rect,
+    sum: Vec<Vec3<f32>>,
+    samples: Vec<u32>,
+}
+
+impl TileData {
+    fn gather(state: &RenderState, rect: Rect) -> Self {
+        let index: Vec<usize> = rect.pixels().map(|(x, y)| y * state.width + x).collect();
+        Self {
+            rect,
+            sum: index.iter().map(|&i| state.sum[i]).collect(),
+            samples: index.iter().map(|&i| state.samples[i]).collect(),
+        }
+    }
+
+    fn scatter(&self, state: &mut RenderState) {
+        for (k, (x, y)) in self.rect.pixels().enumerate() {
+            let i = y * state.width + x;
+            state.sum[i] = self.sum[k];
+            state.samples[i] = self.samples[k];
+        }
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self.rect;
+        for v in [x, y, width, height] {
+            write_u32(w, v as u32)?;
+        }
+        for (c, n) in self.sum.iter().zip(&self.samples) {
+            write_u32(w, *n)?;
+            for v in [c.x(), c.y(), c.z()] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(r: &mut impl Read) -> Result<Self> {
+        let mut v = [0; 4];
+        for v in &mut v {
+            *v = read_u32(r)? as usize;
+        }
+        let rect = Rect::new(v[0], v[1], v[2], v[3]);
+        let mut sum = Vec::new();
+        let mut samples = Vec::new();
+        for _ in 0..rect.area() {
+            samples.push(read_u32(r)?);
+            let mut c = [0.0; 3];
+            for c in &mut c {
+                *c = f32::from_bits(read_u32(r)?);
+            }
+            sum.push(Vec3::new(c[0], c[1], c[2]));
+        }
+        Ok(Self { rect, sum, samples })
+    }
+}
+
+fn write_stats(w: &mut impl Write, stats: &RenderStats) -> Result<()> {
+    for v in [
+        stats.primary_rays,
+        stats.secondary_rays,
+        stats.shadow_rays,
+        stats.intersection_tests,
+        stats.bvh_node_visits,
+    ] {
+        write_u64(w, v)?;
+    }
+    write_u32(w, stats.path_lengths.len() as u32)?;
+    for &n in &stats.path_lengths {
+        write_u64(w, n)?;
+    }
+    Ok(())
+}
+
+fn read_stats(r: &mut impl Read) -> Result<RenderStats> {
+    let mut v = [0; 5];
+    for v in &mut v {
+        *v = read_u64(r)?;
+    }
+    let len = read_u32(r)?;
+    let path_lengths = (0..len).map(|_| read_u64(r)).collect::<Result<_>>()?;
+    Ok(RenderStats {
+        primary_rays: v[0],
+        secondary_rays: v[1],
+        shadow_rays: v[2],
+        intersection_tests: v[3],
+        bvh_node_visits: v[4],
+        path_lengths,
+        ..RenderStats::default()
+    })
+}
+
+/// Connection to a worker process that runs [`serve`], usually another copy of the
+/// renderer with the same scene, on this machine or another one.
+pub struct Worker {
+    addr: String,
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Worker {
+    /// Connects with [`DEFAULT_TIMEOUT`].
+    pub fn connect(addr: &str) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut worker = Self {
+            addr: addr.to_string(),
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        };
+        worker.set_timeout(DEFAULT_TIMEOUT)?;
+        Ok(worker)
+    }
+
+    /// Longest wait for the worker to take or answer a message. A worker that stalls
+    /// longer fails like one that disconnected, so a tile takes at most this long.
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        let stream = self.writer.get_ref();
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    // waits for the answer to a message, turning ERROR into an error
+    fn reply(&mut self, tag: u8) -> Result<()> {
+        match read_u8(&mut self.reader)? {
+            t if t == tag => Ok(()),
+            ERROR => {
+                let len = read_u32(&mut self.reader)? as usize;
+                let mut msg = vec![0; len.min(4096)];
+                self.reader.read_exact(&mut msg)?;
+                Err(Error::other(format!(
+                    "worker {}: {}",
+                    self.addr,
+                    String::from_utf8_lossy(&msg)
+                )))
+            }
+            _ => Err(invalid("unexpected message from worker")),
+        }
+    }
+
+    // makes sure the worker renders the same image
+    fn hello(&mut self, state: &RenderState) -> Result<()> {
+        self.writer.write_all(&[HELLO])?;
+        for v in [
+            VERSION,
+            state.width as u32,
+            state.height as u32,
+            state.max_depth as u32,
+        ] {
+            write_u32(&mut self.writer, v)?;
+        }
+        self.writer.flush()?;
+        self.reply(HELLO)
+    }
+
+    fn job(
+        &mut self,
+        seed: u64,
+        target: usize,
+        per_pass: usize,
+        tile: &TileData,
+    ) -> Result<(TileData, RenderStats)> {
+        self.writer.write_all(&[JOB])?;
+        write_u64(&mut self.writer, seed)?;
+        write_u64(&mut self.writer, target.min(u32::MAX as usize) as u64)?;
+        write_u64(&mut self.writer, per_pass as u64)?;
+        tile.write(&mut self.writer)?;
+        self.writer.flush()?;
+        self.reply(JOB)?;
+        let done = TileData::read(&mut self.reader)?;
+        if done.rect != tile.rect {
+            return Err(invalid("worker returned the wrong tile"));
+        }
+        // exactly the samples serve adds, so a bad worker can't stall or undo the render
+        let expected =
+            |before: u32| before as usize + per_pass.min(target.saturating_sub(before as usize));
+        let added = tile
+            .samples
+            .iter()
+            .zip(&done.samples)
+            .all(|(&before, &after)| after as usize == expected(before));
+        if !added {
+            return Err(invalid("worker returned the wrong sample counts"));
+        }
+        Ok((done, read_stats(&mut self.reader)?))
+    }
+}
+
+fn send_error(w: &mut impl Write, msg: &str) -> Result<()> {
+    w.write_all(&[ERROR])?;
+    write_u32(w, msg.len() as u32)?;
+    w.write_all(msg.as_bytes())?;
+    w.flush()
+}
+
+/// Renders tiles for one coordinator connected on `stream` until it hangs up. The camera
+/// and scene have to be set up the same way as the coordinator's, only the image size
+/// and depth can be checked.
+pub fn serve(stream: TcpStream, camera: &Camera, scene: &Scene) -> Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(DEFAULT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let (own_width, own_height) = camera.image_size();
+    let own_depth = camera.max_depth();
+    let frame = Rect::new(0, 0, own_width, own_height);
+    loop {
+        // the coordinator may take its time between messages, not within one
+        reader.get_ref().set_read_timeout(None)?;
+        let mut tag = [0; 1];
+        if reader.read(&mut tag)? == 0 {
+            return Ok(());
+        }
+        reader.get_ref().set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+        match tag[0] {
+            HELLO => {
+                let version = read_u32(&mut reader)?;
+                let (width, height) = (read_u32(&mut reader)?, read_u32(&mut reader)?);
+                let depth = read_u32(&mut reader)?;
+                let size = (width as usize, height as usize, depth as usize);
+                if version != VERSION {
+                    let msg = format!("protocol version {version}, expected {VERSION}");
+                    send_error(&mut writer, &msg)?;
+                } else if size != (own_width, own_height, own_depth) {
+                    let msg = format!(
+                        "coordinator renders {width}x{height} at depth {depth}, \
+                         this worker {}x{} at depth {}",
+                        own_width, own_height, own_depth
+                    );
+                    send_error(&mut writer, &msg)?;
+                } else {
+                    writer.write_all(&[HELLO])?;
+                    writer.flush()?;
+                }
+            }
+            JOB => {
+                let seed = read_u64(&mut reader)?;
+                let target = read_u64(&mut reader)? as usize;
+                let per_pass = read_u64(&mut reader)? as usize;
+                let mut tile = TileData::read(&mut reader)?;
+                if tile.rect.intersect(&frame) != tile.rect {
+                    send_error(&mut writer, "tile outside the image")?;
+                    continue;
+                }
+                stats::take();
+                for (k, pixel) in tile.rect.pixels().enumerate() {
+                    let taken = &mut tile.samples[k];
+                    let n = per_pass.min(target.saturating_sub(*taken as usize)) as u32;
+                    camera.render_pixel(scene, seed, pixel, &mut tile.sum[k], taken, n);
+                }
+                writer.write_all(&[JOB])?;
+                tile.write(&mut writer)?;
+                write_stats(&mut writer, &stats::take())?;
+                writer.flush()?;
+            }
+            _ => return Err(invalid("unknown message from coordinator")),
+        }
+    }
+}
+
+impl Camera {
+    /// Like [`Camera::resume`] but hands the tiles of every pass out to `workers`, which
+    /// render them in parallel. Tiles carry their sums, so the result is the same as a
+    /// render on one machine.
+    ///
+    /// A worker that fails or stalls past its timeout is dropped from `workers` and its
+    /// tile goes to another one.
+    /// Fails if a worker renders a different image size or depth, or when no worker is
+    /// left.
+    pub fn render_distributed(
+        &self,
+        state: RenderState,
+        settings: &Progressive,
+        workers: &mut Vec<Worker>,
+        preview: impl FnMut(&Framebuffer),
+    ) -> Result<Framebuffer> {
+        for worker in workers.iter_mut() {
+            worker.hello(&state)?;
+        }
+        self.accumulate(
+            state,
+            settings,
+            preview,
+            |state, target, per_pass, session| {
+                self.distribute_pass(workers, state, target, per_pass, session)
+            },
+        )
+    }
+
+    fn distribute_pass(
+        &self,
+        workers: &mut Vec<Worker>,
+        state: &mut RenderState,
+        target: usize,
+        per_pass: usize,
+        session: &mut Session,
+    ) -> Result<()> {
+        // popped from the back, so reversed to hand them out in order
+        let queue: Vec<Rect> = self
+            .tiles()
+            .into_iter()
+            .rev()
+            .filter(|t| {
+                t.pixels()
+                    .any(|(x, y)| (state.samples[y * state.width + x] as usize) < target)
+            })
+            .collect();
+        let queue = Mutex::new(queue);
+        let seed = state.seed;
+
+        while !queue.lock().unwrap().is_empty() {
+            if workers.is_empty() {
+                return Err(Error::other("all render workers failed"));
+            }
+            let (tx, rx) = mpsc::channel();
+            let shared: &RenderState = state;
+            let mut finished = Vec::new();
+            let alive: Vec<bool> = thread::scope(|s| {
+                let handles: Vec<_> = workers
+                    .iter_mut()
+                    .map(|worker| {
+                        let (tx, queue) = (tx.clone(), &queue);
+                        s.spawn(move || loop {
+                            let Some(rect) = queue.lock().unwrap().pop() else {
+                                return true;
+                            };
+                            let tile = TileData::gather(shared, rect);
+                            match worker.job(seed, target, per_pass, &tile) {
+                                Ok((done, stats)) => tx.send((tile, done, stats)).unwrap(),
+                                Err(_) => {
+                                    // someone else picks the tile up
+                                    queue.lock().unwrap().push(rect);
+                                    return false;
+                                }
+                            }
+                        })
+                    })
+                    .collect();
+                // tiles are merged once the workers stop reading the state, progress and
+                // stats can't wait that long
+                drop(tx);
+                for (tile, done, worker_stats) in rx {
+                    let count = |t: &TileData| t.samples.iter().map(|&n| n as u64).sum::<u64>();
+                    // counted as if rendered here, so the pass picks them up
+                    stats::add(&worker_stats);
+                    session.tile_done(count(&done) - count(&tile));
+                    finished.push(done);
+                }
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            for done in finished {
+                done.scatter(state);
+            }
+            let mut alive = alive.into_iter();
+            workers.retain(|_| alive.next().unwrap());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, rc::Rc, thread::JoinHandle};
+
+    use super::*;
+    use crate::{material::Lambertian, HittableList, Sphere};
+
+    fn scene() -> Scene {
+        let mut world = HittableList::default();
+        world.add(Box::new(Sphere {
+            center: Vec3::new(0.0, 0.0, -1.0),
+            radius: 0.5,
+            material: Rc::new(Box::new(Lambertian {
+                albedo: Vec3::new(0.5, 0.5, 0.5),
+            })),
+        }));
+        let mut scene = Scene::new(world);
+        scene.add_sphere_light(Vec3::new(0.0, 2.0, 0.0), 0.5, Vec3::new(4.0, 4.0, 4.0));
+        scene
+    }
+
+    fn camera() -> Camera {
+        let mut camera = Camera::initialize();
+        camera.set_image_size(12, 8);
+        camera.set_max_depth(4);
+        camera.set_seed(3);
+        camera.set_tiles(4, Default::default());
+        camera
+    }
+
+    fn settings() -> Progressive {
+        Progressive {
+            samples_per_pass: 2,
+            target_samples: 5,
+            preview_interval: 0,
+            ..Progressive::default()
+        }
+    }
+
+    // a worker on a loopback port, answering jobs with handle until the coordinator goes
+    fn spawn(handle: fn(TcpStream) -> Result<()>) -> (Worker, JoinHandle<Result<()>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let thread = thread::spawn(move || handle(listener.accept()?.0));
+        (Worker::connect(&addr).unwrap(), thread)
+    }
+
+    fn good(stream: TcpStream) -> Result<()> {
+        serve(stream, &camera(), &scene())
+    }
+
+    // says hello like serve but hands every tile back without adding samples
+    fn lazy(stream: TcpStream) -> Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        while let Ok(tag) = read_u8(&mut reader) {
+            if tag == HELLO {
+                for _ in 0..4 {
+                    read_u32(&mut reader)?;
+                }
+                writer.write_all(&[HELLO])?;
+            } else {
+                for _ in 0..3 {
+                    read_u64(&mut reader)?;
+                }
+                let tile = TileData::read(&mut reader)?;
+                writer.write_all(&[JOB])?;
+                tile.write(&mut writer)?;
+                write_stats(&mut writer, &RenderStats::default())?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn pixels(framebuffer: &Framebuffer) -> Vec<[u32; 3]> {
+        let bits = |c: &Vec3<f32>| [c.x(), c.y(), c.z()].map(f32::to_bits);
+        framebuffer.pixels.iter().map(bits).collect()
+    }
+
+    #[test]
+    fn matches_a_local_render() {
+        let mut camera = camera();
+        let (a, a_thread) = spawn(good);
+        let (b, b_thread) = spawn(good);
+        let mut workers = vec![a, b];
+        for crop in [None, Some(Rect::new(3, 2, 7, 5))] {
+            camera.set_crop(crop);
+            let local = camera
+                .resume(&scene(), camera.render_state(), &settings(), |_| {})
+                .unwrap();
+            let distributed = camera
+                .render_distributed(camera.render_state(), &settings(), &mut workers, |_| {})
+                .unwrap();
+            assert_eq!(workers.len(), 2);
+            assert_eq!(pixels(&distributed), pixels(&local));
+            let counts = |f: &Framebuffer| RenderStats {
+                wall_time: Duration::ZERO,
+                ..f.metadata.stats.clone()
+            };
+            assert_eq!(counts(&distributed), counts(&local));
+        }
+        drop(workers);
+        a_thread.join().unwrap().unwrap();
+        b_thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn workers_returning_wrong_samples_are_dropped() {
+        let camera = camera();
+        let local = camera.resume(&scene(), camera.render_state(), &settings(), |_| {});
+
+        let (worker, lazy_thread) = spawn(lazy);
+        let mut workers = vec![worker];
+        let result =
+            camera.render_distributed(camera.render_state(), &settings(), &mut workers, |_| {});
+        assert!(result.is_err());
+        assert!(workers.is_empty());
+        lazy_thread.join().unwrap().unwrap();
+
+        let (lazy_worker, lazy_thread) = spawn(lazy);
+        let (good_worker, good_thread) = spawn(good);
+        let mut workers = vec![lazy_worker, good_worker];
+        let distributed = camera
+            .render_distributed(camera.render_state(), &settings(), &mut workers, |_| {})
+            .unwrap();
+        // whichever tiles the lazy one took were rendered again by the other
+        assert_eq!(pixels(&distributed), pixels(&local.unwrap()));
+        drop(workers);
+        lazy_thread.join().unwrap().unwrap();
+        good_thread.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use std::{f32::consts::PI, io::Result, path::Path};
+
+use crate::{
+    hdr::HdrImage,
+    light::{Light, LightSample},
+    random_double,
+    sampling::Distribution2D,
+    vec3::Vec3,
+};
+
+// equirectangular image around the scene, u wraps around +y starting behind the camera,
+// v goes from straight up to straight down. the image center is looking down -z
+pub struct EnvironmentLight {
+    image: HdrImage,
+    distribution: Distribution2D,
+    // radians around +y
+    rotation: f32,
+    intensity: f32,
+}
+
+#[inline(always)]
+fn luminance(c: Vec3<f32>) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+impl EnvironmentLight {
+    pub fn new(image: HdrImage, rotation_degrees: f32, intensity: f32) -> Self {
+        // weight by sin(theta), rows near the poles cover less solid angle
+        let func: Vec<f32> = (0..image.height)
+            .flat_map(|y| {
+                let sin_theta = (PI * (y as f32 + 0.5) / image.height as f32).sin();
+                let row = &image.data[y * image.width..(y + 1) * image.width];
+                row.iter().map(move |&c| luminance(c) * sin_theta)
+            })
+            .collect();
+        let distribution = Distribution2D::new(&func, image.width, image.height);
+        Self {
+            image,
+            distribution,
+            rotation: rotation_degrees.to_radians(),
+            intensity,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>, rotation_degrees: f32, intensity: f32) -> Result<Self> {
+        Ok(Self::new(
+            HdrImage::load(path)?,
+            rotation_degrees,
+            intensity,
+        ))
+    }
+
+    fn direction_to_uv(&self, dir: Vec3<f32>) -> (f32, f32) {
+        let dir = dir / dir.length_squared().sqrt();
+        let theta = dir.y().clamp(-1.0, 1.0).acos();
+        let phi = dir.x().atan2(-dir.z()) - self.rotation;
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        (u, theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f32, v: f32) -> (Vec3<f32>, f32) {
+        let theta = v * PI;
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation;
+        let sin_theta = theta.sin();
+        (
+            Vec3::new(sin_theta * phi.sin(), theta.cos(), -sin_theta * phi.cos()),
+            sin_theta,
+        )
+    }
+
+    fn lookup(&self, u: f32, v: f32) -> Vec3<f32> {
+        let x = (u * self.image.width as f32) as usize;
+        let y = (v * self.image.height as f32) as usize;
+        self.image.get(x, y) * self.intensity
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample_li(&self, _p: Vec3<f32>) -> Option<LightSample> {
+        let ((u, v), map_pdf) = self
+            .distribution
+            .sample_continuous(random_double(), random_double());
+        if map_pdf == 0.0 {
+            return None;
+        }
+        let (wi, sin_theta) = self.uv_to_direction(u, v);
+        if sin_theta == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi,
+            li: self.lookup(u, v),
+            // jacobian of the equirectangular mapping
+            pdf: map_pdf / (2.0 * PI * PI * sin_theta),
+            distance: f32::INFINITY,
+        })
+    }
+
+    fn pdf_li(&self, _p: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        let (u, v) = self.direction_to_uv(wi);
+        let sin_theta = (v * PI).sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn le(&self, dir: Vec3<f32>) -> Vec3<f32> {
+        let (u, v) = self.direction_to_uv(dir);
+        self.lookup(u, v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // dim sky with one bright texel, rotated so nothing lines up with the axes
+    fn environment() -> EnvironmentLight {
+        let (width, height) = (16, 8);
+        let mut data = vec![Vec3::new(0.2, 0.3, 0.5); width * height];
+        data[2 * width + 5] = Vec3::new(50.0, 40.0, 30.0);
+        let image = HdrImage {
+            width,
+            height,
+            data,
+        };
+        EnvironmentLight::new(image, 30.0, 1.0)
+    }
+
+    #[test]
+    fn uv_round_trips_through_directions() {
+        let env = environment();
+        for (u, v) in [(0.1, 0.2), (0.5, 0.5), (0.93, 0.71)] {
+            let (dir, sin_theta) = env.uv_to_direction(u, v);
+            assert!((sin_theta - (v * PI).sin()).abs() < 1e-6);
+            let (u2, v2) = env.direction_to_uv(dir);
+            assert!((u - u2).abs() < 1e-4 && (v - v2).abs() < 1e-4, "{u2} {v2}");
+        }
+    }
+
+    #[test]
+    fn samples_match_the_solid_angle_density() {
+        let env = environment();
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        // theta, phi midpoint grid, the pdf is piecewise constant in both
+        let (nt, np) = (800, 1600);
+        let mut total = 0.0;
+        for i in 0..nt {
+            let theta = PI * (i as f32 + 0.5) / nt as f32;
+            let mut ring = 0.0;
+            for j in 0..np {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / np as f32;
+                let wi = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                ring += env.pdf_li(p, wi) as f64;
+            }
+            total += ring * (theta.sin() * PI * 2.0 * PI) as f64 / (nt * np) as f64;
+        }
+        assert!((total - 1.0).abs() < 1e-2, "{total}");
+
+        let n = 100_000;
+        let (mut matched, mut radiance) = (0, 0.0);
+        for _ in 0..n {
+            let s = env.sample_li(p).unwrap();
+            if (s.pdf - env.pdf_li(p, s.wi)).abs() < 1e-2 * s.pdf {
+                matched += 1;
+            }
+            radiance += (s.li.x() / s.pdf) as f64 / n as f64;
+        }
+        // samples right on a texel edge may round trip into the neighbour
+        assert!(matched * 1000 > n * 999, "{matched}");
+
+        // the estimate of the integral of le over the sphere, texel by texel
+        let expected: f64 = (0..8)
+            .map(|y| {
+                let theta = |y: usize| PI * y as f32 / 8.0;
+                let ring = 2.0 * PI * (theta(y).cos() - theta(y + 1).cos()) / 16.0;
+                let row: f32 = (0..16).map(|x| env.image.get(x, y).x()).sum();
+                (row * ring) as f64
+            })
+            .sum();
+        assert!(
+            (radiance - expected).abs() < 2e-2 * expected,
+            "{radiance} {expected}"
+        );
+    }
+}
@@ -0,0 +1,789 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    aabb::Aabb,
+    hdr::HdrImage,
+    instance::Instance,
+    json::Json,
+    mat4::{Mat4, Quat, Trs},
+    material::{DiffuseLight, Material},
+    mesh::{MeshData, TriangleMesh},
+    png,
+    principled::Principled,
+    texture::{Channel, ImageTexture, Product, SolidColor, Texture, VertexColor},
+    vec3::Vec3,
+    Hittable,
+};
+
+// gltf 2.0 scenes, .gltf with external or embedded buffers and binary .glb
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+//
+// brings in the node hierarchy, triangle primitives with normals, first uvs and vertex
+// colors, metallic roughness materials with their png textures and the first perspective
+// camera. skins, morph targets, animations and normal maps are ignored
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// where the file's camera sits, ready for Camera::look_at
+pub struct GltfCamera {
+    pub from: Vec3<f32>,
+    pub at: Vec3<f32>,
+    pub up: Vec3<f32>,
+    pub vfov: f32,
+}
+
+pub struct GltfScene {
+    pub objects: Vec<Box<dyn Hittable>>,
+    pub camera: Option<GltfCamera>,
+    // world space bounds of everything in objects
+    pub bounds: Aabb,
+    // things that were skipped, the scene still renders without them
+    pub warnings: Vec<String>,
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<GltfScene> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    let (json, bin) = if bytes.starts_with(b"glTF") {
+        split_glb(&bytes)?
+    } else {
+        (&bytes[..], None)
+    };
+    let text = std::str::from_utf8(json).map_err(|_| invalid("gltf json is not utf-8"))?;
+    let root = Json::parse(text)?;
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    Loader::new(root, base, bin)?.scene()
+}
+
+// 12 byte header then chunks of length, type and data. json comes first, an optional
+// binary chunk backs the first buffer
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let word = |i: usize| -> Result<usize> {
+        let b = bytes
+            .get(i..i + 4)
+            .ok_or_else(|| invalid("truncated glb"))?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    if word(4)? != 2 {
+        return Err(invalid("only glb version 2 is supported"));
+    }
+    let end = word(8)?.min(bytes.len());
+    let (mut json, mut bin) = (None, None);
+    let mut pos = 12;
+    while pos + 8 <= end {
+        let (len, kind) = (word(pos)?, word(pos + 4)?);
+        let data = bytes
+            .get(pos + 8..pos + 8 + len)
+            .ok_or_else(|| invalid("truncated glb chunk"))?;
+        match kind {
+            0x4e4f534a if json.is_none() => json = Some(data),
+            0x004e4942 if bin.is_none() => bin = Some(data),
+            _ => {}
+        }
+        pos += 8 + len;
+    }
+    Ok((json.ok_or_else(|| invalid("glb has no json chunk"))?, bin))
+}
+
+fn base64(text: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            _ => return Err(invalid("bad base64 data")),
+        };
+        acc = acc << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+// relative uris may be percent encoded
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// a vecN / matN accessor read into floats, components per element alongside
+struct Accessor {
+    values: Vec<f64>,
+    components: usize,
+}
+
+impl Accessor {
+    fn vec3(&self, i: usize) -> Vec3<f32> {
+        let v = &self.values[i * self.components..];
+        Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32)
+    }
+
+    fn len(&self) -> usize {
+        self.values.len() / self.components
+    }
+}
+
+fn component_size(component_type: usize) -> Result<usize> {
+    match component_type {
+        5120 | 5121 => Ok(1),
+        5122 | 5123 => Ok(2),
+        5125 | 5126 => Ok(4),
+        _ => Err(invalid("unknown gltf component type")),
+    }
+}
+
+fn read_component(bytes: &[u8], component_type: usize, normalized: bool) -> f64 {
+    let b4 = || [bytes[0], bytes[1], bytes[2], bytes[3]];
+    match (component_type, normalized) {
+        (5120, false) => bytes[0] as i8 as f64,
+        (5120, true) => (bytes[0] as i8 as f64 / 127.0).max(-1.0),
+        (5121, false) => bytes[0] as f64,
+        (5121, true) => bytes[0] as f64 / 255.0,
+        (5122, false) => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+        (5122, true) => (i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 32767.0).max(-1.0),
+        (5123, false) => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+        (5123, true) => u16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 65535.0,
+        (5125, _) => u32::from_le_bytes(b4()) as f64,
+        _ => f32::from_le_bytes(b4()) as f64,
+    }
+}
+
+// materials by material index and whether the primitive has vertex colors
+type MaterialCache = HashMap<(Option<usize>, bool), Rc<Box<dyn Material>>>;
+
+struct Loader {
+    root: Json,
+    base: PathBuf,
+    buffers: Vec<Vec<u8>>,
+    // decoded images by index and whether they hold srgb color
+    images: HashMap<(usize, bool), Option<Rc<HdrImage>>>,
+    materials: MaterialCache,
+    // one mesh per primitive, shared between the nodes using it
+    meshes: HashMap<usize, Vec<Rc<dyn Hittable>>>,
+    warnings: Vec<String>,
+}
+
+impl Loader {
+    fn new(root: Json, base: PathBuf, bin: Option<&[u8]>) -> Result<Self> {
+        let mut buffers = Vec::new();
+        for (i, buffer) in root["buffers"].as_array().iter().enumerate() {
+            let data = match buffer.get("uri").and_then(Json::as_str) {
+                Some(uri) if uri.starts_with("data:") => {
+                    let (_, data) = uri
+                        .split_once(";base64,")
+                        .ok_or_else(|| invalid("only base64 data uris are supported"))?;
+                    base64(data)?
+                }
+                Some(uri) => fs::read(base.join(decode_uri(uri)))?,
+                None if i == 0 => bin
+                    .ok_or_else(|| invalid("gltf buffer has no uri and no glb chunk"))?
+                    .to_vec(),
+                None => return Err(invalid("gltf buffer has no uri")),
+            };
+            buffers.push(data);
+        }
+        Ok(Self {
+            root,
+            base,
+            buffers,
+            images: HashMap::new(),
+            materials: HashMap::new(),
+            meshes: HashMap::new(),
+            warnings: Vec::new(),
+        })
+    }
+
+    fn warn(&mut self, msg: String) {
+        if !self.warnings.contains(&msg) {
+            self.warnings.push(msg);
+        }
+    }
+
+    // an entry of one of the top level arrays
+    fn item(&self, list: &str, index: usize) -> Result<&Json> {
+        self.root[list]
+            .as_array()
+            .get(index)
+            .ok_or_else(|| invalid(&format!("gltf {list} index {index} out of range")))
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>)> {
+        let view = self.item("bufferViews", index)?;
+        let buffer = view["buffer"]
+            .as_usize()
+            .and_then(|b| self.buffers.get(b))
+            .ok_or_else(|| invalid("gltf buffer view has a bad buffer"))?;
+        let offset = view["byteOffset"].as_usize().unwrap_or(0);
+        let length = view["byteLength"].as_usize().unwrap_or(0);
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| invalid("gltf buffer view out of range"))?;
+        Ok((data, view["byteStride"].as_usize()))
+    }
+
+    // count elements of components each starting at offset into a buffer view
+    fn read_elements(
+        &self,
+        view: usize,
+        offset: usize,
+        count: usize,
+        components: usize,
+        component_type: usize,
+        normalized: bool,
+    ) -> Result<Vec<f64>> {
+        let out_of_range = || invalid("gltf accessor out of range");
+        let (data, stride) = self.buffer_view(view)?;
+        let size = component_size(component_type)?;
+        let element = size * components;
+        let stride = stride.unwrap_or(element);
+        if stride < element {
+            return Err(invalid("gltf buffer view stride smaller than its elements"));
+        }
+        // the last element has to fit in the view, which also bounds the allocation
+        if count > 0 {
+            let end = (count - 1)
+                .checked_mul(stride)
+                .and_then(|n| n.checked_add(offset))
+                .and_then(|n| n.checked_add(element))
+                .ok_or_else(out_of_range)?;
+            if end > data.len() {
+                return Err(out_of_range());
+            }
+        }
+        let mut values = Vec::with_capacity(count * components);
+        for i in 0..count {
+            let start = offset + i * stride;
+            for bytes in data[start..start + element].chunks_exact(size) {
+                values.push(read_component(bytes, component_type, normalized));
+            }
+        }
+        Ok(values)
+    }
+
+    fn accessor(&self, index: usize) -> Result<Accessor> {
+        let a = self.item("accessors", index)?;
+        let components = match a["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(invalid("unknown gltf accessor type")),
+        };
+        let count = a["count"].as_usize().unwrap_or(0);
+        let component_type = a["componentType"].as_usize().unwrap_or(0);
+        let normalized = a["normalized"].as_bool().unwrap_or(false);
+        let mut values = match a["bufferView"].as_usize() {
+            Some(view) => self.read_elements(
+                view,
+                a["byteOffset"].as_usize().unwrap_or(0),
+                count,
+                components,
+                component_type,
+                normalized,
+            )?,
+            // no view means zeros, usually with sparse values on top. nothing is stored to
+            // bound the count, so it may be no more than the buffers could hold
+            None => {
+                let total: usize = self.buffers.iter().map(Vec::len).sum();
+                let size = component_size(component_type)?;
+                match count.checked_mul(components * size) {
+                    Some(bytes) if bytes <= total => vec![0.0; count * components],
+                    _ => return Err(invalid("gltf accessor larger than its buffers")),
+                }
+            }
+        };
+
+        // a few elements replaced, mostly seen on morph targets
+        let sparse = &a["sparse"];
+        if let Some(n) = sparse["count"].as_usize() {
+            let (idx, vals) = (&sparse["indices"], &sparse["values"]);
+            let view = |j: &Json| {
+                j["bufferView"]
+                    .as_usize()
+                    .ok_or_else(|| invalid("gltf sparse accessor without a buffer view"))
+            };
+            let indices = self.read_elements(
+                view(idx)?,
+                idx["byteOffset"].as_usize().unwrap_or(0),
+                n,
+                1,
+                idx["componentType"].as_usize().unwrap_or(0),
+                false,
+            )?;
+            let replacements = self.read_elements(
+                view(vals)?,
+                vals["byteOffset"].as_usize().unwrap_or(0),
+                n,
+                components,
+                component_type,
+                normalized,
+            )?;
+            for (k, &i) in indices.iter().enumerate() {
+                let i = i as usize;
+                if i < count {
+                    values[i * components..(i + 1) * components]
+                        .copy_from_slice(&replacements[k * components..(k + 1) * components]);
+                }
+            }
+        }
+        Ok(Accessor { values, components })
+    }
+
+    fn image(&mut self, index: usize, srgb: bool) -> Option<Rc<HdrImage>> {
+        if let Some(image) = self.images.get(&(index, srgb)) {
+            return image.clone();
+        }
+        let image = match self.decode_image(index, srgb) {
+            Ok(image) => Some(Rc::new(image)),
+            Err(e) => {
+                self.warn(format!("image {index} skipped: {e}"));
+                None
+            }
+        };
+        self.images.insert((index, srgb), image.clone());
+        image
+    }
+
+    fn decode_image(&self, index: usize, srgb: bool) -> Result<HdrImage> {
+        let image = self.item("images", index)?;
+        let bytes = match (image["uri"].as_str(), image["bufferView"].as_usize()) {
+            (Some(uri), _) if uri.starts_with("data:") => {
+                let (_, data) = uri
+                    .split_once(";base64,")
+                    .ok_or_else(|| invalid("only base64 data uris are supported"))?;
+                base64(data)?
+            }
+            (Some(uri), _) => fs::read(self.base.join(decode_uri(uri)))?,
+            (None, Some(view)) => self.buffer_view(view)?.0.to_vec(),
+            _ => return Err(invalid("gltf image has no data")),
+        };
+        // only png is decoded, jpeg textures fall back to the plain factors
+        let mut image = png::read(&bytes)?;
+        if srgb {
+            for c in &mut image.data {
+                *c = Vec3::new(
+                    srgb_to_linear(c.x()),
+                    srgb_to_linear(c.y()),
+                    srgb_to_linear(c.z()),
+                );
+            }
+        }
+        Ok(image)
+    }
+
+    // the image behind a textureInfo, None if there isn't one or it couldn't be read
+    fn texture(&mut self, info: &Json, srgb: bool) -> Option<Rc<dyn Texture>> {
+        let texture = info["index"].as_usize()?;
+        if info["texCoord"].as_usize().unwrap_or(0) != 0 {
+            self.warn(format!(
+                "texture {texture} uses a second uv set, only the first is read"
+            ));
+        }
+        let source = self.item("textures", texture).ok()?["source"].as_usize()?;
+        Some(ImageTexture::shared(self.image(source, srgb)?))
+    }
+
+    fn material(&mut self, index: Option<usize>, vertex_colors: bool) -> Rc<Box<dyn Material>> {
+        if let Some(m) = self.materials.get(&(index, vertex_colors)) {
+            return m.clone();
+        }
+        let json = index.and_then(|i| self.item("materials", i).ok()).cloned();
+        let m = match json {
+            Some(json) => self.build_material(&json, vertex_colors),
+            None => {
+                let grey = SolidColor::shared(Vec3::new(0.8, 0.8, 0.8));
+                let base = if vertex_colors {
+                    VertexColor::shared(grey)
+                } else {
+                    grey
+                };
+                Rc::new(Box::new(Principled::new(base)) as Box<dyn Material>)
+            }
+        };
+        self.materials.insert((index, vertex_colors), m.clone());
+        m
+    }
+
+    fn build_material(&mut self, json: &Json, vertex_colors: bool) -> Rc<Box<dyn Material>> {
+        let factor3 = |j: &Json, default: f32| {
+            j.as_f32_array()
+                .filter(|v| v.len() >= 3)
+                .map_or(Vec3::new(default, default, default), |v| {
+                    Vec3::new(v[0], v[1], v[2])
+                })
+        };
+
+        // emissive surfaces become lights, they aren't sampled directly
+        let strength = json["extensions"]["KHR_materials_emissive_strength"]["emissiveStrength"]
+            .as_f32()
+            .unwrap_or(1.0);
+        let emissive = factor3(&json["emissiveFactor"], 0.0) * strength;
+        if emissive.length_squared() > 0.0 {
+            return Rc::new(Box::new(DiffuseLight { emit: emissive }));
+        }
+
+        let pbr = &json["pbrMetallicRoughness"];
+        let mut base = SolidColor::shared(factor3(&pbr["baseColorFactor"], 1.0));
+        if let Some(t) = self.texture(&pbr["baseColorTexture"], true) {
+            base = Product::shared(t, base);
+        }
+        if vertex_colors {
+            base = Product::shared(VertexColor::shared(SolidColor::scalar(1.0)), base);
+        }
+
+        // roughness in green, metal in blue
+        let metallic_factor = SolidColor::scalar(pbr["metallicFactor"].as_f32().unwrap_or(1.0));
+        let roughness_factor = SolidColor::scalar(pbr["roughnessFactor"].as_f32().unwrap_or(1.0));
+        let (metallic, roughness) = match self.texture(&pbr["metallicRoughnessTexture"], false) {
+            Some(t) => (
+                Product::shared(Channel::shared(t.clone(), 2), metallic_factor),
+                Product::shared(Channel::shared(t, 1), roughness_factor),
+            ),
+            None => (metallic_factor, roughness_factor),
+        };
+
+        let mut material = Principled {
+            metallic,
+            roughness,
+            ..Principled::new(base)
+        };
+        let ext = &json["extensions"];
+        if let Some(t) = ext["KHR_materials_transmission"]["transmissionFactor"].as_f32() {
+            material.transmission = SolidColor::scalar(t);
+        }
+        if let Some(ior) = ext["KHR_materials_ior"]["ior"].as_f32() {
+            material.ior = ior;
+        }
+        let clearcoat = &ext["KHR_materials_clearcoat"];
+        if let Some(c) = clearcoat["clearcoatFactor"].as_f32() {
+            material.clearcoat = SolidColor::scalar(c);
+            let r = clearcoat["clearcoatRoughnessFactor"]
+                .as_f32()
+                .unwrap_or(0.0);
+            material.clearcoat_gloss = SolidColor::scalar(1.0 - r);
+        }
+        if json["normalTexture"].get("index").is_some() {
+            self.warn("normal maps are ignored".to_string());
+        }
+        Rc::new(Box::new(material))
+    }
+
+    // a vertex attribute, checked to have at least the components it is read with
+    fn attribute(&self, index: usize, name: &str, components: usize) -> Result<Accessor> {
+        let accessor = self.accessor(index)?;
+        if accessor.components < components {
+            return Err(invalid(&format!(
+                "gltf {name} needs {components} components, its accessor has {}",
+                accessor.components
+            )));
+        }
+        Ok(accessor)
+    }
+
+    fn primitive(&mut self, json: &Json) -> Result<Option<Rc<dyn Hittable>>> {
+        let mode = json["mode"].as_usize().unwrap_or(4);
+        if !(4..=6).contains(&mode) {
+            self.warn(format!(
+                "point and line primitives (mode {mode}) are skipped"
+            ));
+            return Ok(None);
+        }
+        let attributes = &json["attributes"];
+        let position = attributes["POSITION"]
+            .as_usize()
+            .ok_or_else(|| invalid("gltf primitive without positions"))?;
+        let positions = self.attribute(position, "POSITION", 3)?;
+        let mut data = MeshData {
+            positions: (0..positions.len()).map(|i| positions.vec3(i)).collect(),
+            ..MeshData::default()
+        };
+        if let Some(i) = attributes["NORMAL"].as_usize() {
+            let normals = self.attribute(i, "NORMAL", 3)?;
+            data.normals = (0..normals.len()).map(|i| normals.vec3(i)).collect();
+        }
+        // gltf puts v = 0 at the top of the image
+        if let Some(i) = attributes["TEXCOORD_0"].as_usize() {
+            let uvs = self.attribute(i, "TEXCOORD_0", 2)?;
+            data.uvs = uvs
+                .values
+                .chunks_exact(uvs.components)
+                .map(|uv| (uv[0] as f32, 1.0 - uv[1] as f32))
+                .collect();
+        }
+        if let Some(i) = attributes["COLOR_0"].as_usize() {
+            let colors = self.attribute(i, "COLOR_0", 3)?;
+            data.colors = (0..colors.len()).map(|i| colors.vec3(i)).collect();
+        }
+
+        let indices: Vec<usize> = match json["indices"].as_usize() {
+            Some(i) => self
+                .accessor(i)?
+                .values
+                .iter()
+                .map(|&v| v as usize)
+                .collect(),
+            None => (0..data.positions.len()).collect(),
+        };
+        data.triangles = match mode {
+            // strips flip every other triangle to keep the winding
+            5 => (0..indices.len().saturating_sub(2))
+                .map(|i| {
+                    let t = [indices[i], indices[i + 1], indices[i + 2]];
+                    if i % 2 == 0 {
+                        t
+                    } else {
+                        [t[1], t[0], t[2]]
+                    }
+                })
+                .collect(),
+            6 => (1..indices.len().saturating_sub(1))
+                .map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+            _ => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+        };
+        if data.triangles.is_empty() {
+            return Ok(None);
+        }
+
+        let material = self.material(json["material"].as_usize(), !data.colors.is_empty());
+        Ok(Some(Rc::new(TriangleMesh::new(data, material))))
+    }
+
+    fn mesh(&mut self, index: usize) -> Result<Vec<Rc<dyn Hittable>>> {
+        if let Some(mesh) = self.meshes.get(&index) {
+            return Ok(mesh.clone());
+        }
+        let primitives = self.item("meshes", index)?["primitives"]
+            .as_array()
+            .to_vec();
+        let mut mesh = Vec::new();
+        for p in &primitives {
+            mesh.extend(self.primitive(p)?);
+        }
+        self.meshes.insert(index, mesh.clone());
+        Ok(mesh)
+    }
+
+    // a node's transform relative to its parent, matrix or separate parts
+    fn local_transform(node: &Json) -> Mat4 {
+        if let Some(m) = node["matrix"].as_f32_array().filter(|m| m.len() == 16) {
+            // column major
+            let mut out = Mat4::IDENTITY;
+            for (col, values) in m.chunks_exact(4).enumerate() {
+                for (row, &v) in values.iter().enumerate() {
+                    out.0[row][col] = v;
+                }
+            }
+            return out;
+        }
+        let vec3 = |j: &Json, default: f32| {
+            j.as_f32_array()
+                .filter(|v| v.len() == 3)
+                .map_or(Vec3::new(default, default, default), |v| {
+                    Vec3::new(v[0], v[1], v[2])
+                })
+        };
+        // stored x, y, z, w
+        let rotation = node["rotation"]
+            .as_f32_array()
+            .filter(|q| q.len() == 4)
+            .map_or(Quat::identity(), |q| Quat {
+                w: q[3],
+                v: Vec3::new(q[0], q[1], q[2]),
+            });
+        Trs::new(
+            vec3(&node["translation"], 0.0),
+            rotation,
+            vec3(&node["scale"], 1.0),
+        )
+        .matrix()
+    }
+
+    fn visit(
+        &mut self,
+        node: usize,
+        parent: Mat4,
+        depth: usize,
+        scene: &mut GltfScene,
+    ) -> Result<()> {
+        // the hierarchy has to be a tree, this stops cycles in broken files
+        if depth > 256 {
+            return Err(invalid("gltf node hierarchy too deep"));
+        }
+        let json = self.item("nodes", node)?.clone();
+        let world = parent * Self::local_transform(&json);
+
+        if let Some(mesh) = json["mesh"].as_usize() {
+            if world.inverse().is_none() {
+                self.warn(format!("node {node} has a singular transform"));
+            } else {
+                for object in self.mesh(mesh)? {
+                    let object = Instance::new(object, world);
+                    scene.bounds = Aabb::enclosing(&scene.bounds, &object.bounding_box());
+                    scene.objects.push(Box::new(object));
+                }
+            }
+        }
+
+        if let (Some(camera), None) = (json["camera"].as_usize(), &scene.camera) {
+            let camera = self.item("cameras", camera)?;
+            match camera["perspective"]["yfov"].as_f32() {
+                Some(yfov) => {
+                    let from = world.transform_point(Vec3::new(0.0, 0.0, 0.0));
+                    scene.camera = Some(GltfCamera {
+                        from,
+                        at: from + world.transform_vector(Vec3::new(0.0, 0.0, -1.0)),
+                        up: world.transform_vector(Vec3::new(0.0, 1.0, 0.0)),
+                        vfov: yfov.to_degrees(),
+                    });
+                }
+                None => self.warn("orthographic cameras are ignored".to_string()),
+            }
+        }
+
+        for child in json["children"].as_array() {
+            let child = child
+                .as_usize()
+                .ok_or_else(|| invalid("bad gltf child node"))?;
+            self.visit(child, world, depth + 1, scene)?;
+        }
+        Ok(())
+    }
+
+    fn scene(mut self) -> Result<GltfScene> {
+        // the default scene, else the first, else every node nobody lists as a child
+        let roots: Vec<usize> = match self.root["scenes"].as_array() {
+            [] => {
+                let nodes = self.root["nodes"].as_array();
+                let children: Vec<usize> = nodes
+                    .iter()
+                    .flat_map(|n| n["children"].as_array().iter().filter_map(Json::as_usize))
+                    .collect();
+                (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+            }
+            scenes => {
+                let index = self.root["scene"].as_usize().unwrap_or(0);
+                let scene = scenes
+                    .get(index)
+                    .ok_or_else(|| invalid("gltf scene index out of range"))?;
+                scene["nodes"]
+                    .as_array()
+                    .iter()
+                    .filter_map(Json::as_usize)
+                    .collect()
+            }
+        };
+
+        let mut scene = GltfScene {
+            objects: Vec::new(),
+            camera: None,
+            bounds: Aabb::EMPTY,
+            warnings: Vec::new(),
+        };
+        for root in roots {
+            self.visit(root, Mat4::IDENTITY, 0, &mut scene)?;
+        }
+        scene.warnings = self.warnings;
+        Ok(scene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one triangle in a 36 byte buffer, accessor is spliced into the accessors
+    fn triangle(accessor: &str) -> Result<GltfScene> {
+        let text = format!(
+            r#"{{
+                "buffers": [{{
+                    "byteLength": 36,
+                    "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+                }}],
+                "bufferViews": [{{"buffer": 0, "byteLength": 36}}],
+                "accessors": [{accessor}],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}}}]}}],
+                "nodes": [{{"mesh": 0}}],
+                "scenes": [{{"nodes": [0]}}]
+            }}"#
+        );
+        Loader::new(Json::parse(&text)?, PathBuf::new(), None)?.scene()
+    }
+
+    #[test]
+    fn loads_a_triangle() {
+        let scene =
+            triangle(r#"{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}"#)
+                .unwrap();
+        assert_eq!(scene.objects.len(), 1);
+        assert!(scene.warnings.is_empty());
+    }
+
+    #[test]
+    fn rejects_counts_past_the_buffer() {
+        for accessor in [
+            r#"{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3"}"#,
+            r#"{"bufferView": 0, "componentType": 5126, "count": 1e17, "type": "VEC3"}"#,
+            r#"{"bufferView": 0, "byteOffset": 1e19, "componentType": 5126, "count": 3, "type": "VEC3"}"#,
+            r#"{"componentType": 5126, "count": 1e17, "type": "VEC3"}"#,
+        ] {
+            let e = triangle(accessor).err().expect(accessor);
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn rejects_attributes_with_too_few_components() {
+        let accessor = r#"{"bufferView": 0, "componentType": 5126, "count": 9, "type": "SCALAR"}"#;
+        assert!(triangle(accessor).is_err());
+    }
+}
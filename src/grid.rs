@@ -0,0 +1,304 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+    rc::Rc,
+};
+
+use crate::{
+    aabb::Aabb, interval::Interval, material::Material, random_double, ray::Ray, vec3::Vec3,
+    HitRecord, Hittable,
+};
+
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// dense scalar grid, x varies fastest then y then z
+pub struct VoxelGrid {
+    pub nx: usize,
+    pub ny: usize,
+    pub nz: usize,
+    pub data: Vec<f32>,
+}
+
+impl VoxelGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f32>) -> Self {
+        assert_eq!(
+            data.len(),
+            nx * ny * nz,
+            "voxel data doesn't match the resolution"
+        );
+        Self { nx, ny, nz, data }
+    }
+
+    // headerless little endian f32 values
+    pub fn load_raw(path: impl AsRef<Path>, nx: usize, ny: usize, nz: usize) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        let size = nx
+            .checked_mul(ny)
+            .and_then(|n| n.checked_mul(nz))
+            .and_then(|n| n.checked_mul(4));
+        if size != Some(bytes.len()) {
+            return Err(invalid("raw grid size doesn't match the resolution"));
+        }
+        Ok(Self::new(nx, ny, nz, le_floats(&bytes)))
+    }
+
+    // mitsuba .vol: "VOL" 3, encoding, resolution, channels, bounding box, then the data.
+    // only float32 is supported and extra channels are dropped
+    pub fn load_vol(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read_vol(&fs::read(path)?)
+    }
+
+    // the same from bytes already in memory
+    pub fn read_vol(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 48 || &bytes[0..4] != b"VOL\x03" {
+            return Err(invalid("missing VOL header"));
+        }
+        let header: Vec<i32> = bytes[4..24]
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if header[0] != 1 {
+            return Err(invalid("only float32 volumes are supported"));
+        }
+        if header[1..].iter().any(|&n| n <= 0) {
+            return Err(invalid("bad volume resolution"));
+        }
+        let (nx, ny, nz, channels) = (
+            header[1] as usize,
+            header[2] as usize,
+            header[3] as usize,
+            header[4] as usize,
+        );
+        // the header is checked against the file before anything is sized from it
+        let count = nx
+            .checked_mul(ny)
+            .and_then(|n| n.checked_mul(nz))
+            .ok_or_else(|| invalid("bad volume resolution"))?;
+        let size = count
+            .checked_mul(channels)
+            .and_then(|n| n.checked_mul(4))
+            .ok_or_else(|| invalid("bad volume resolution"))?;
+        if bytes.len() - 48 < size {
+            return Err(invalid("truncated volume data"));
+        }
+        let data = le_floats(&bytes[48..48 + size])
+            .into_iter()
+            .step_by(channels)
+            .collect();
+        Ok(Self::new(nx, ny, nz, data))
+    }
+
+    // .vol files carry their resolution, anything else is raw and needs it passed in
+    pub fn load(path: impl AsRef<Path>, resolution: Option<(usize, usize, usize)>) -> Result<Self> {
+        let path = path.as_ref();
+        let is_vol = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("vol"));
+        match (is_vol, resolution) {
+            (true, _) => Self::load_vol(path),
+            (false, Some((nx, ny, nz))) => Self::load_raw(path, nx, ny, nz),
+            (false, None) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("raw grid {} needs a resolution", path.display()),
+            )),
+        }
+    }
+
+    pub fn max_value(&self) -> f32 {
+        self.data.iter().fold(0.0, |m, &d| m.max(d))
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.data[(z * self.ny + y) * self.nx + x]
+    }
+
+    // trilinear lookup with p in [0, 1]^3, values sit at voxel centers
+    pub fn lookup(&self, p: Vec3<f32>) -> f32 {
+        let coord = |v: f32, n: usize| {
+            let x = (v * n as f32 - 0.5).clamp(0.0, (n - 1) as f32);
+            let i = (x as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), x - i as f32)
+        };
+        let (x0, x1, fx) = coord(p.x(), self.nx);
+        let (y0, y1, fy) = coord(p.y(), self.ny);
+        let (z0, z1, fz) = coord(p.z(), self.nz);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |z| {
+            lerp(
+                lerp(self.voxel(x0, y0, z), self.voxel(x1, y0, z), fx),
+                lerp(self.voxel(x0, y1, z), self.voxel(x1, y1, z), fx),
+                fy,
+            )
+        };
+        lerp(plane(z0), plane(z1), fz)
+    }
+}
+
+fn le_floats(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+// heterogeneous volume with the grid stretched over an axis aligned box. free flights are
+// sampled against the largest density with delta tracking, shadow rays use ratio tracking
+// https://cs.dartmouth.edu/~wjarosz/publications/novak18monte.html
+pub struct GridMedium {
+    grid: VoxelGrid,
+    bounds: Aabb,
+    density_scale: f32,
+    majorant: f32,
+    phase_function: Rc<Box<dyn Material>>,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: VoxelGrid,
+        corner_a: Vec3<f32>,
+        corner_b: Vec3<f32>,
+        density_scale: f32,
+        phase_function: Box<dyn Material>,
+    ) -> Self {
+        let majorant = grid.max_value() * density_scale;
+        Self {
+            grid,
+            bounds: Aabb::from_points(corner_a, corner_b),
+            density_scale,
+            majorant,
+            phase_function: Rc::new(phase_function),
+        }
+    }
+
+    pub fn density(&self, p: Vec3<f32>) -> f32 {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let local = Vec3::new(
+            (p.x() - min.x()) / (max.x() - min.x()),
+            (p.y() - min.y()) / (max.y() - min.y()),
+            (p.z() - min.z()) / (max.z() - min.z()),
+        );
+        self.grid.lookup(local) * self.density_scale
+    }
+
+    // tentative collisions with the majorant, calls f with each t until it returns false
+    // or the ray leaves the box
+    fn track(&self, r: &Ray<f32>, ray_t: Interval, mut f: impl FnMut(f32) -> bool) {
+        if self.majorant <= 0.0 {
+            return;
+        }
+        let Some(span) = self.bounds.hit(r, ray_t) else {
+            return;
+        };
+        let step = 1.0 / (self.majorant * f32_len!(r.direction().length_squared()));
+        let mut t = span.min;
+        loop {
+            t -= (1.0 - random_double()).ln() * step;
+            if t >= span.max || !f(t) {
+                return;
+            }
+        }
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let mut collision = None;
+        self.track(r, ray_t, |t| {
+            if random_double() < self.density(r.at(t)) / self.majorant {
+                collision = Some(t);
+                return false;
+            }
+            true
+        });
+        let Some(t) = collision else {
+            return false;
+        };
+        *rec = HitRecord {
+            p: r.at(t),
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            t,
+            u: 0.0,
+            v: 0.0,
+            front_face: true,
+            material: Some(material),
+            color: None,
+        };
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.phase_function.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn transmittance(&self, r: &Ray<f32>, ray_t: Interval) -> f32 {
+        let mut transmittance = 1.0;
+        self.track(r, ray_t, |t| {
+            transmittance *= 1.0 - self.density(r.at(t)) / self.majorant;
+            transmittance > 0.0
+        });
+        transmittance.max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vol(resolution: [i32; 4], values: &[f32]) -> Vec<u8> {
+        let mut bytes = b"VOL\x03".to_vec();
+        for n in [1].into_iter().chain(resolution) {
+            bytes.extend(n.to_le_bytes());
+        }
+        for v in [0.0f32, 0.0, 0.0, 1.0, 1.0, 1.0]
+            .into_iter()
+            .chain(values.iter().copied())
+        {
+            bytes.extend(v.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn keeps_the_first_channel() {
+        let grid = VoxelGrid::read_vol(&vol([2, 1, 1, 2], &[1.0, 9.0, 2.0, 9.0])).unwrap();
+        assert_eq!((grid.nx, grid.ny, grid.nz), (2, 1, 1));
+        assert_eq!(grid.data, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn rejects_bad_resolutions() {
+        for resolution in [
+            [2, 1, 1, 3],
+            [0, 1, 1, 1],
+            [-2, -1, 1, 1],
+            [i32::MAX, i32::MAX, i32::MAX, i32::MAX],
+            [65536, 65536, 65536, 1],
+        ] {
+            let bytes = vol(resolution, &[1.0, 2.0, 3.0, 4.0]);
+            assert!(VoxelGrid::read_vol(&bytes).is_err(), "{resolution:?}");
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Result},
+    path::Path,
+};
+
+use crate::vec3::Vec3;
+
+// linear float rgb image, rows top to bottom
+pub struct HdrImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<Vec3<f32>>,
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+impl HdrImage {
+    pub fn get(&self, x: usize, y: usize) -> Vec3<f32> {
+        self.data[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+
+    // picks the format from the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let mut reader = BufReader::new(File::open(path)?);
+        match ext.as_str() {
+            "hdr" | "pic" => Self::read_hdr(&mut reader),
+            "pfm" => Self::read_pfm(&mut reader),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unknown hdr image format {}", path.display()),
+            )),
+        }
+    }
+
+    // radiance rgbe, flat or new style run length encoded scanlines
+    // https://www.graphics.cornell.edu/~bjw/rgbe.html
+    pub fn read_hdr<R: BufRead>(reader: &mut R) -> Result<Self> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err(invalid("missing radiance header"));
+        }
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid("truncated radiance header"));
+            }
+            let l = line.trim();
+            if l.is_empty() {
+                break;
+            }
+            if let Some(format) = l.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(invalid("only 32-bit_rle_rgbe is supported"));
+                }
+            }
+        }
+        line.clear();
+        reader.read_line(&mut line)?;
+        let res: Vec<&str> = line.split_whitespace().collect();
+        let (height, width) = match res.as_slice() {
+            ["-Y", h, "+X", w] => (
+                h.parse::<usize>().map_err(|_| invalid("bad height"))?,
+                w.parse::<usize>().map_err(|_| invalid("bad width"))?,
+            ),
+            _ => return Err(invalid("only -Y h +X w orientation is supported")),
+        };
+        if width == 0 || height == 0 {
+            return Err(invalid("empty image"));
+        }
+
+        // grown as scanlines arrive, a bogus size runs out of data before memory
+        let mut data = Vec::new();
+        for _ in 0..height {
+            Self::read_scanline(reader, width, &mut data)?;
+        }
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    fn read_scanline<R: Read>(
+        reader: &mut R,
+        width: usize,
+        data: &mut Vec<Vec3<f32>>,
+    ) -> Result<()> {
+        let mut first = [0u8; 4];
+        reader.read_exact(&mut first)?;
+        let rle =
+            (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+        if !rle {
+            data.push(rgbe_to_float(first));
+            let mut pixel = [0u8; 4];
+            for _ in 1..width {
+                reader.read_exact(&mut pixel)?;
+                data.push(rgbe_to_float(pixel));
+            }
+            return Ok(());
+        }
+        if ((first[2] as usize) << 8 | first[3] as usize) != width {
+            return Err(invalid("scanline width mismatch"));
+        }
+        let mut scanline = vec![[0u8; 4]; width];
+
+        // each channel is run length encoded separately
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let mut count = [0u8; 1];
+                reader.read_exact(&mut count)?;
+                let count = count[0] as usize;
+                if count > 128 {
+                    let run = count - 128;
+                    if x + run > width {
+                        return Err(invalid("run overflows scanline"));
+                    }
+                    let mut value = [0u8; 1];
+                    reader.read_exact(&mut value)?;
+                    scanline[x..x + run]
+                        .iter_mut()
+                        .for_each(|p| p[channel] = value[0]);
+                    x += run;
+                } else {
+                    if count == 0 || x + count > width {
+                        return Err(invalid("bad scanline literal"));
+                    }
+                    let mut values = vec![0u8; count];
+                    reader.read_exact(&mut values)?;
+                    for (p, v) in scanline[x..x + count].iter_mut().zip(values) {
+                        p[channel] = v;
+                    }
+                    x += count;
+                }
+            }
+        }
+        data.extend(scanline.iter().map(|&rgbe| rgbe_to_float(rgbe)));
+        Ok(())
+    }
+
+    // portable float map, "PF" rgb or "Pf" grayscale, rows bottom to top
+    pub fn read_pfm<R: BufRead>(reader: &mut R) -> Result<Self> {
+        let mut header = Vec::new();
+        // magic, dimensions and scale are whitespace separated tokens
+        while header.len() < 4 {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid("truncated pfm header"));
+            }
+            header.extend(line.split_whitespace().map(|s| s.to_string()));
+        }
+        let channels = match header[0].as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid("missing pfm magic")),
+        };
+        let width: usize = header[1].parse().map_err(|_| invalid("bad width"))?;
+        let height: usize = header[2].parse().map_err(|_| invalid("bad height"))?;
+        let scale: f32 = header[3].parse().map_err(|_| invalid("bad scale"))?;
+        let little_endian = scale < 0.0;
+        if width == 0 || height == 0 {
+            return Err(invalid("empty image"));
+        }
+
+        // read pixel by pixel, a bogus size runs out of data before memory
+        let mut data = Vec::new();
+        let mut bytes = [0u8; 12];
+        for _ in 0..height {
+            for _ in 0..width {
+                let pixel = &mut bytes[..channels * 4];
+                reader.read_exact(pixel)?;
+                let f = |i: usize| {
+                    let b = [
+                        pixel[i * 4],
+                        pixel[i * 4 + 1],
+                        pixel[i * 4 + 2],
+                        pixel[i * 4 + 3],
+                    ];
+                    if little_endian {
+                        f32::from_le_bytes(b)
+                    } else {
+                        f32::from_be_bytes(b)
+                    }
+                };
+                data.push(if channels == 3 {
+                    Vec3::new(f(0), f(1), f(2))
+                } else {
+                    Vec3::new(f(0), f(0), f(0))
+                });
+            }
+        }
+        // stored bottom to top
+        let data = data.chunks_exact(width).rev().flatten().copied().collect();
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+fn rgbe_to_float(rgbe: [u8; 4]) -> Vec3<f32> {
+    if rgbe[3] == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    let f = 2.0_f32.powi(rgbe[3] as i32 - (128 + 8));
+    Vec3::new(
+        (rgbe[0] as f32 + 0.5) * f,
+        (rgbe[1] as f32 + 0.5) * f,
+        (rgbe[2] as f32 + 0.5) * f,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &[u8] = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n";
+
+    #[test]
+    fn flat_and_run_length_scanlines() {
+        let mut file = HEADER.to_vec();
+        file.extend(b"-Y 2 +X 8\n");
+        // a flat row, then the same colour run length encoded channel by channel
+        for _ in 0..8 {
+            file.extend([128, 64, 0, 129]);
+        }
+        file.extend([2, 2, 0, 8]);
+        for value in [128, 64, 0, 129] {
+            file.extend([128 + 8, value]);
+        }
+        let image = HdrImage::read_hdr(&mut &file[..]).unwrap();
+        assert_eq!((image.width, image.height, image.data.len()), (8, 2, 16));
+        for c in &image.data {
+            assert_eq!(
+                [c.x(), c.y(), c.z()],
+                [128.5 / 128.0, 64.5 / 128.0, 0.5 / 128.0]
+            );
+        }
+    }
+
+    #[test]
+    fn pfm_rows_are_flipped() {
+        let mut file = b"Pf\n1 2\n-1.0\n".to_vec();
+        file.extend(1.0f32.to_le_bytes());
+        file.extend(2.0f32.to_le_bytes());
+        let image = HdrImage::read_pfm(&mut &file[..]).unwrap();
+        assert_eq!([image.get(0, 0).x(), image.get(0, 1).x()], [2.0, 1.0]);
+    }
+
+    #[test]
+    fn oversized_headers_fail_without_allocating() {
+        let mut hdr = HEADER.to_vec();
+        hdr.extend(b"-Y 3000000000 +X 3000000000\n");
+        assert!(HdrImage::read_hdr(&mut &hdr[..]).is_err());
+        for pfm in [
+            &b"PF\n3000000000 3000000000\n-1.0\n"[..],
+            b"PF\n0 4\n-1.0\n",
+        ] {
+            assert!(HdrImage::read_pfm(&mut &pfm[..]).is_err());
+        }
+    }
+}
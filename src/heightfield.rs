@@ -0,0 +1,359 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Result},
+    path::Path,
+    rc::Rc,
+};
+
+use crate::{
+    aabb::Aabb, hdr::HdrImage, interval::Interval, material::Material,
+    primitives::intersect_triangle, ray::Ray, vec3::Vec3, HitRecord, Hittable,
+};
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// grid of heights over the xz plane, nx samples across x and nz rows along z, x fastest.
+// each cell is two triangles. rays walk the cells with a 2d dda and skip any cell whose
+// height range they pass over or under
+pub struct Heightfield {
+    nx: usize,
+    nz: usize,
+    heights: Vec<f32>,
+    normals: Vec<Vec3<f32>>,
+    // lowest and highest sample of each cell
+    cell_range: Vec<(f32, f32)>,
+    corner: Vec3<f32>,
+    cell_x: f32,
+    cell_z: f32,
+    material: Rc<Box<dyn Material>>,
+    bbox: Aabb,
+}
+
+// a hit with one triangle of a cell, its corners as grid indices and the barycentrics
+#[derive(Clone, Copy)]
+struct TriangleHit {
+    t: f32,
+    tri: [(usize, usize); 3],
+    b1: f32,
+    b2: f32,
+}
+
+impl Heightfield {
+    // the grid spans extent.x by extent.z from corner, samples are scaled by extent.y
+    pub fn new(
+        nx: usize,
+        nz: usize,
+        samples: &[f32],
+        corner: Vec3<f32>,
+        extent: Vec3<f32>,
+        material: Rc<Box<dyn Material>>,
+    ) -> Self {
+        let (size_x, height, size_z) = (extent.x(), extent.y(), extent.z());
+        assert!(
+            nx >= 2 && nz >= 2,
+            "a heightfield needs at least 2x2 samples"
+        );
+        assert_eq!(
+            samples.len(),
+            nx * nz,
+            "height samples don't match the resolution"
+        );
+        let heights: Vec<f32> = samples.iter().map(|&s| corner.y() + s * height).collect();
+        let cell_x = size_x / (nx - 1) as f32;
+        let cell_z = size_z / (nz - 1) as f32;
+
+        let h = |x: usize, z: usize| heights[z * nx + x];
+        let mut normals = Vec::with_capacity(nx * nz);
+        for z in 0..nz {
+            for x in 0..nx {
+                // central differences, one sided at the edges
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(nx - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(nz - 1));
+                let dx = (h(x1, z) - h(x0, z)) / ((x1 - x0) as f32 * cell_x);
+                let dz = (h(x, z1) - h(x, z0)) / ((z1 - z0) as f32 * cell_z);
+                let n = Vec3::new(-dx, 1.0, -dz);
+                normals.push(n / n.length_squared().sqrt());
+            }
+        }
+
+        let mut cell_range = Vec::with_capacity((nx - 1) * (nz - 1));
+        let (mut lo, mut hi) = (f32::INFINITY, f32::NEG_INFINITY);
+        for z in 0..nz - 1 {
+            for x in 0..nx - 1 {
+                let corners = [h(x, z), h(x + 1, z), h(x, z + 1), h(x + 1, z + 1)];
+                let min = corners.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = corners.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                cell_range.push((min, max));
+                lo = lo.min(min);
+                hi = hi.max(max);
+            }
+        }
+        let bbox = Aabb::from_points(
+            Vec3::new(corner.x(), lo, corner.z()),
+            Vec3::new(corner.x() + size_x, hi, corner.z() + size_z),
+        )
+        .padded();
+
+        Self {
+            nx,
+            nz,
+            heights,
+            normals,
+            cell_range,
+            corner,
+            cell_x,
+            cell_z,
+            material,
+            bbox,
+        }
+    }
+
+    // samples from a function over [0, 1]^2
+    pub fn from_fn(
+        nx: usize,
+        nz: usize,
+        f: impl Fn(f32, f32) -> f32,
+        corner: Vec3<f32>,
+        extent: Vec3<f32>,
+        material: Rc<Box<dyn Material>>,
+    ) -> Self {
+        let samples: Vec<f32> = (0..nz)
+            .flat_map(|z| (0..nx).map(move |x| (x, z)))
+            .map(|(x, z)| f(x as f32 / (nx - 1) as f32, z as f32 / (nz - 1) as f32))
+            .collect();
+        Self::new(nx, nz, &samples, corner, extent, material)
+    }
+
+    // grayscale image with the top row at -z, values in [0, 1]. pgm is read directly,
+    // pfm and hdr through HdrImage using the red channel
+    pub fn load(
+        path: impl AsRef<Path>,
+        corner: Vec3<f32>,
+        extent: Vec3<f32>,
+        material: Rc<Box<dyn Material>>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let is_pgm = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("pgm"));
+        let (nx, nz, samples) = if is_pgm {
+            read_pgm(&mut BufReader::new(File::open(path)?))?
+        } else {
+            let image = HdrImage::load(path)?;
+            let samples = image.data.iter().map(|c| c.x()).collect();
+            (image.width, image.height, samples)
+        };
+        if nx < 2 || nz < 2 {
+            return Err(invalid("heightfield image needs at least 2x2 pixels"));
+        }
+        Ok(Self::new(nx, nz, &samples, corner, extent, material))
+    }
+
+    fn vertex(&self, x: usize, z: usize) -> Vec3<f32> {
+        Vec3::new(
+            self.corner.x() + x as f32 * self.cell_x,
+            self.heights[z * self.nx + x],
+            self.corner.z() + z as f32 * self.cell_z,
+        )
+    }
+
+    // closest hit with the two triangles of a cell
+    fn hit_cell(&self, r: &Ray<f32>, x: usize, z: usize, ray_t: &Interval) -> Option<HitRecord> {
+        let ids = [(x, z), (x + 1, z), (x, z + 1), (x + 1, z + 1)];
+        let mut best: Option<TriangleHit> = None;
+        for tri in [[ids[0], ids[2], ids[1]], [ids[1], ids[2], ids[3]]] {
+            let limit = Interval::new(ray_t.min, best.map_or(ray_t.max, |b| b.t));
+            let [a, b, c] = tri.map(|(x, z)| self.vertex(x, z));
+            if let Some((t, b1, b2)) = intersect_triangle(r, a, b, c, &limit) {
+                best = Some(TriangleHit { t, tri, b1, b2 });
+            }
+        }
+        let TriangleHit { t, tri, b1, b2 } = best?;
+        let n = |(x, z): (usize, usize)| self.normals[z * self.nx + x];
+        let normal = n(tri[0]) * (1.0 - b1 - b2) + n(tri[1]) * b1 + n(tri[2]) * b2;
+        let p = r.at(t);
+        let mut rec = HitRecord {
+            p,
+            normal: normal / normal.length_squared().sqrt(),
+            t,
+            u: (p.x() - self.corner.x()) / (self.cell_x * (self.nx - 1) as f32),
+            v: (p.z() - self.corner.z()) / (self.cell_z * (self.nz - 1) as f32),
+            front_face: false,
+            material: None,
+            color: None,
+        };
+        rec.set_face_normal(r, rec.normal);
+        Some(rec)
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let Some(span) = self.bbox.hit(r, ray_t) else {
+            return false;
+        };
+        let (o, d) = (r.origin(), r.direction());
+        let start = r.at(span.min);
+        let cell = |v: f32, origin: f32, size: f32, n: usize| {
+            (((v - origin) / size).floor().max(0.0) as usize).min(n - 2)
+        };
+        let mut x = cell(start.x(), self.corner.x(), self.cell_x, self.nx);
+        let mut z = cell(start.z(), self.corner.z(), self.cell_z, self.nz);
+
+        // ray parameter of the next cell boundary on an axis and the step between them
+        let setup = |dir: f32, origin: f32, pos: f32, index: usize, size: f32| {
+            if dir > 0.0 {
+                (
+                    1,
+                    (pos + (index + 1) as f32 * size - origin) / dir,
+                    size / dir,
+                )
+            } else if dir < 0.0 {
+                (-1, (pos + index as f32 * size - origin) / dir, -size / dir)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, mut next_x, delta_x) = setup(d.x(), o.x(), self.corner.x(), x, self.cell_x);
+        let (step_z, mut next_z, delta_z) = setup(d.z(), o.z(), self.corner.z(), z, self.cell_z);
+
+        let mut t_enter = span.min;
+        loop {
+            let t_exit = next_x.min(next_z).min(span.max);
+            let (lo, hi) = self.cell_range[z * (self.nx - 1) + x];
+            let (y0, y1) = (r.at(t_enter).y(), r.at(t_exit).y());
+            if y0.min(y1) <= hi && y0.max(y1) >= lo {
+                if let Some(mut found) = self.hit_cell(r, x, z, &ray_t) {
+                    found.material = Some(material);
+                    *rec = found;
+                    return true;
+                }
+            }
+            if t_exit >= span.max {
+                return false;
+            }
+            if next_x < next_z {
+                if (step_x < 0 && x == 0) || (step_x > 0 && x == self.nx - 2) {
+                    return false;
+                }
+                x = (x as i64 + step_x) as usize;
+                next_x += delta_x;
+            } else {
+                if (step_z < 0 && z == 0) || (step_z > 0 && z == self.nz - 2) {
+                    return false;
+                }
+                z = (z as i64 + step_z) as usize;
+                next_z += delta_z;
+            }
+            t_enter = t_exit;
+        }
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// binary P5 or ascii P2 graymap, 8 or 16 bit, normalized by maxval
+fn read_pgm<R: BufRead>(reader: &mut R) -> Result<(usize, usize, Vec<f32>)> {
+    let mut tokens = Vec::new();
+    // magic, width, height and maxval, comments run to the end of the line
+    while tokens.len() < 4 {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("truncated pgm header"));
+        }
+        let content = line.split('#').next().unwrap_or("");
+        tokens.extend(content.split_whitespace().map(|s| s.to_string()));
+    }
+    let binary = match tokens[0].as_str() {
+        "P5" => true,
+        "P2" => false,
+        _ => return Err(invalid("missing pgm magic")),
+    };
+    let width: usize = tokens[1].parse().map_err(|_| invalid("bad width"))?;
+    let height: usize = tokens[2].parse().map_err(|_| invalid("bad height"))?;
+    let maxval: u32 = tokens[3].parse().map_err(|_| invalid("bad maxval"))?;
+    if maxval == 0 || maxval > 65535 {
+        return Err(invalid("bad maxval"));
+    }
+    let count = width
+        .checked_mul(height)
+        .ok_or_else(|| invalid("pgm image too large"))?;
+    let scale = 1.0 / maxval as f32;
+
+    let values: Vec<f32> = if binary {
+        let wide = maxval > 255;
+        let size = count
+            .checked_mul(if wide { 2 } else { 1 })
+            .ok_or_else(|| invalid("pgm image too large"))?;
+        // grown as the data arrives rather than sized from the header
+        let mut bytes = Vec::new();
+        reader.take(size as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != size {
+            return Err(invalid("truncated pgm data"));
+        }
+        if wide {
+            bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 * scale)
+                .collect()
+        } else {
+            bytes.iter().map(|&b| b as f32 * scale).collect()
+        }
+    } else {
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest)?;
+        tokens[4..]
+            .iter()
+            .map(|s| s.as_str())
+            .chain(rest.split_whitespace())
+            .take(count)
+            .map(|s| s.parse::<u32>().map(|v| v as f32 * scale))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| invalid("bad pgm sample"))?
+    };
+    if values.len() != count {
+        return Err(invalid("truncated pgm data"));
+    }
+    Ok((width, height, values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_and_wide_binary_graymaps() {
+        let (w, h, v) = read_pgm(&mut &b"P2\n# a comment\n2 2\n4\n0 1\n2 4\n"[..]).unwrap();
+        assert_eq!((w, h, v), (2, 2, vec![0.0, 0.25, 0.5, 1.0]));
+        let mut pgm = b"P5 2 1 1000\n".to_vec();
+        pgm.extend([0, 0, 3, 232]);
+        assert_eq!(read_pgm(&mut &pgm[..]).unwrap().2, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn bogus_headers_fail_without_allocating() {
+        for pgm in [
+            &b"P5\n3000000000 3000000000\n255\n"[..],
+            b"P5\n18446744073709551615 2\n255\n",
+            b"P5\n4000000000 4000000000\n65535\n",
+            b"P2\n3000000000 3000000000\n255\n1 2 3\n",
+        ] {
+            assert!(read_pgm(&mut &pgm[..]).is_err());
+        }
+    }
+}
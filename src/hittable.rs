@@ -0,0 +1,159 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb,
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+/// Where and how a ray met a surface.
+#[derive(Clone)]
+pub struct HitRecord {
+    pub p: Vec3<f32>,
+    pub normal: Vec3<f32>,
+    pub t: f32,
+    pub u: f32,
+    pub v: f32,
+    pub front_face: bool,
+    pub material: Option<Rc<Box<dyn Material>>>,
+    // interpolated vertex color on meshes that have them
+    pub color: Option<Vec3<f32>>,
+}
+
+impl Default for HitRecord {
+    fn default() -> Self {
+        Self {
+            p: Vec3::default(),
+            normal: Vec3::default(),
+            t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            front_face: false,
+            material: None,
+            color: None,
+        }
+    }
+}
+
+impl HitRecord {
+    /// Stores the normal facing against the ray and whether the ray hit the outside.
+    pub fn set_face_normal(&mut self, r: &Ray<f32>, outward_normal: Vec3<f32>) {
+        self.front_face = r.direction().dot(outward_normal) < 0.0;
+        self.normal = if self.front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        }
+    }
+}
+
+/// Anything a ray can intersect.
+///
+/// `hit` fills `rec` with the closest intersection inside `ray_t`, tagging it with
+/// `material`, which callers normally get from [`Hittable::material`].
+pub trait Hittable {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool;
+    fn material(&self) -> Rc<Box<dyn Material>>;
+    // has to cover the object for the whole shutter interval
+    fn bounding_box(&self) -> Aabb;
+
+    // every surface crossing along the whole line of r in increasing t, front_face tells
+    // entering from leaving. stepping through hit works for anything closed
+    fn hit_all(&self, r: &Ray<f32>, hits: &mut Vec<HitRecord>) {
+        let mut t_min = f32::NEG_INFINITY;
+        // guards against surfaces that keep reporting the same point
+        for _ in 0..32 {
+            let mut rec = HitRecord::default();
+            if !self.hit(
+                r,
+                Interval::new(t_min, f32::INFINITY),
+                &mut rec,
+                self.material(),
+            ) {
+                break;
+            }
+            t_min = rec.t + 1e-4 * rec.t.abs().max(1.0);
+            hits.push(rec);
+        }
+    }
+
+    // fraction of light getting through along ray_t, surfaces are opaque
+    fn transmittance(&self, r: &Ray<f32>, ray_t: Interval) -> f32 {
+        let mut rec = HitRecord::default();
+        if self.hit(r, ray_t, &mut rec, self.material()) {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// The objects of a scene, tested one after another.
+#[derive(Default)]
+pub struct HittableList {
+    pub objects: Vec<Box<dyn Hittable>>,
+}
+
+impl HittableList {
+    pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.objects.push(object);
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+
+    /// Closest hit over all objects.
+    pub fn hit(&self, r: &Ray<f32>, ray_t: Interval, hit_record: &mut HitRecord) -> bool {
+        let mut temp_rec = HitRecord::default();
+        let mut hit_anything = false;
+        let mut closest_so_far = ray_t.max;
+
+        stats::count(Counter::IntersectionTests, self.objects.len() as u64);
+        for object in &self.objects {
+            if object.hit(
+                r,
+                Interval {
+                    min: ray_t.min,
+                    max: closest_so_far,
+                },
+                &mut temp_rec,
+                object.material(),
+            ) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                hit_record.p = temp_rec.p;
+                hit_record.normal = temp_rec.normal;
+                hit_record.t = temp_rec.t;
+                hit_record.u = temp_rec.u;
+                hit_record.v = temp_rec.v;
+                hit_record.front_face = temp_rec.front_face;
+                hit_record.material = temp_rec.material.clone();
+                hit_record.color = temp_rec.color;
+            }
+        }
+        hit_anything
+    }
+
+    /// Fraction of light passing along `ray_t`, the product over all objects.
+    pub fn transmittance(&self, r: &Ray<f32>, ray_t: Interval) -> f32 {
+        let mut transmittance = 1.0;
+        for object in &self.objects {
+            stats::count(Counter::IntersectionTests, 1);
+            transmittance *= object.transmittance(r, ray_t);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Result, Write},
+    time::Duration,
+};
+
+use crate::{color::write_color, ppm::Ppm, stats::RenderStats, tile::Rect, vec3::Vec3};
+
+/// How a [`Framebuffer`] was rendered.
+#[derive(Debug, Clone)]
+pub struct RenderMetadata {
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    /// Open and close time of the shutter.
+    pub shutter: (f32, f32),
+    /// Wall clock time spent rendering.
+    pub render_time: Duration,
+    /// Where the framebuffer sits in the full image when only a crop window was
+    /// rendered.
+    pub crop: Option<Rect>,
+    /// Counts of the rendering work, for previews the work so far.
+    pub stats: RenderStats,
+}
+
+/// Linear rgb radiance straight from the renderer, rows top to bottom.
+///
+/// Nothing is clamped or gamma encoded, use [`Framebuffer::to_image`] for display or
+/// [`Framebuffer::write_pfm`] to keep the full range.
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    /// `width * height` pixels.
+    pub pixels: Vec<Vec3<f32>>,
+    pub metadata: RenderMetadata,
+}
+
+impl Framebuffer {
+    pub fn get(&self, x: usize, y: usize) -> Vec3<f32> {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Clamps and gamma encodes to 8 bits the way the ppm output always has.
+    pub fn to_image(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .flat_map(|c| write_color((c.x(), c.y(), c.z())))
+                .collect(),
+        }
+    }
+
+    /// Writes a little endian portable float map, which stores rows bottom to top.
+    pub fn write_pfm(&self, path: &str) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width).rev() {
+            for c in row {
+                for v in [c.x(), c.y(), c.z()] {
+                    out.write_all(&v.to_le_bytes())?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
+
+/// A finished 8 bit rgb image, gamma encoded, rows top to bottom.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// `width * height` pixels of red, green and blue.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Writes a plain text ppm. Fails if the file exists rather than replace it.
+    pub fn write_ppm(&self, path: &str) -> Result<()> {
+        Ppm::new(self.width, self.height, 255, &self.pixels).write(path)
+    }
+
+    /// Like [`Image::write_ppm`] but replaces the file, for previews rewritten while a
+    /// render runs.
+    pub fn overwrite_ppm(&self, path: &str) -> Result<()> {
+        Ppm::new(self.width, self.height, 255, &self.pixels).overwrite(path)
+    }
+}
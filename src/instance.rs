@@ -0,0 +1,115 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb, interval::Interval, mat4::Mat4, material::Material, ray::Ray, HitRecord, Hittable,
+};
+
+// places a shared object in the world. rays are moved into object space instead of
+// moving the object, the direction isn't renormalized so t means the same in both
+pub struct Instance {
+    pub object: Rc<dyn Hittable>,
+    transform: Mat4,
+    inverse: Mat4,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new(object: Rc<dyn Hittable>, transform: Mat4) -> Self {
+        Self {
+            transform,
+            inverse: transform
+                .inverse()
+                .expect("instance transform must be invertible"),
+            bbox: object.bounding_box().transformed(&transform),
+            object,
+        }
+    }
+
+    fn to_object(&self, r: &Ray<f32>) -> Ray<f32> {
+        Ray::with_time(
+            self.inverse.transform_point(r.origin()),
+            self.inverse.transform_vector(r.direction()),
+            r.time(),
+        )
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        if !self.object.hit(&self.to_object(r), ray_t, rec, material) {
+            return false;
+        }
+        rec.p = self.transform.transform_point(rec.p);
+        // the facing is unchanged since d . n is preserved by the inverse transpose
+        let n = self.inverse.transform_normal(rec.normal);
+        rec.normal = n / n.length_squared().sqrt();
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.object.material()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray<f32>, ray_t: Interval) -> f32 {
+        self.object.transmittance(&self.to_object(r), ray_t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, vec3::Vec3, Sphere};
+
+    #[test]
+    fn hits_a_stretched_sphere() {
+        let material: Rc<Box<dyn Material>> = Rc::new(Box::new(Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        }));
+        let sphere = Rc::new(Sphere {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: material.clone(),
+        });
+        // an ellipsoid with semi axes 2, 1, 1 around (0, 0, -5)
+        let center = Vec3::new(0.0, 0.0, -5.0);
+        let instance = Instance::new(
+            sphere,
+            Mat4::translate(center) * Mat4::scale(Vec3::new(2.0, 1.0, 1.0)),
+        );
+        let ray_t = Interval {
+            min: 0.001,
+            max: f32::INFINITY,
+        };
+        for target in [
+            Vec3::new(0.0, 0.0, -5.0),
+            Vec3::new(1.5, 0.3, -5.0),
+            Vec3::new(-1.0, -0.6, -5.0),
+        ] {
+            let r = Ray::new(Vec3::new(0.0, 0.0, 0.0), target);
+            let mut rec = HitRecord::default();
+            assert!(instance.hit(&r, ray_t, &mut rec, material.clone()));
+            // t means the same in world and object space
+            assert!((r.at(rec.t) - rec.p).length() < 1e-4);
+            let q = rec.p - center;
+            let on_surface = (q.x() / 2.0).powi(2) + q.y().powi(2) + q.z().powi(2);
+            assert!((on_surface - 1.0).abs() < 1e-3, "{on_surface}");
+            // the gradient of the implicit surface
+            let g = Vec3::new(q.x() / 4.0, q.y(), q.z());
+            let g = g / g.length();
+            assert!((rec.normal - g).length() < 1e-3 && rec.front_face);
+        }
+        let mut rec = HitRecord::default();
+        let miss = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.5, 0.0, -5.0));
+        assert!(!instance.hit(&miss, ray_t, &mut rec, material));
+    }
+}
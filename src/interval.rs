@@ -1,20 +1,48 @@
-const INTERVAL_UNIVERSE: Interval = Interval {
+pub const INTERVAL_UNIVERSE: Interval = Interval {
     min: f32::NEG_INFINITY,
     max: f32::INFINITY,
 };
 
-const INTERVAL_EMPTY: Interval = Interval {
+pub const INTERVAL_EMPTY: Interval = Interval {
     min: f32::INFINITY,
     max: f32::NEG_INFINITY,
 };
 
-#[derive(Default)]
+#[derive(Default, Debug, Clone, Copy)]
 pub struct Interval {
     pub min: f32,
     pub max: f32,
 }
 
 impl Interval {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    // smallest interval containing both
+    pub fn enclosing(a: Interval, b: Interval) -> Self {
+        Self {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    // the overlap, empty if there is none
+    pub fn intersect(a: Interval, b: Interval) -> Self {
+        Self {
+            min: a.min.max(b.min),
+            max: a.max.min(b.max),
+        }
+    }
+
+    pub fn expand(&self, delta: f32) -> Self {
+        let padding = delta / 2.0;
+        Self {
+            min: self.min - padding,
+            max: self.max + padding,
+        }
+    }
+
     pub fn size(&self) -> f32 {
         self.max - self.min
     }
@@ -0,0 +1,118 @@
+//! A small physically based path tracer.
+//!
+//! Scenes are a [`HittableList`] of objects wrapped in a [`Scene`] together with the
+//! lights sampled for direct lighting. A [`Camera`] renders a scene into a linear
+//! [`Framebuffer`], which is written out as a separate step:
+//!
+//! ```no_run
+//! use std::rc::Rc;
+//!
+//! use raytracer::{
+//!     material::Lambertian, scene::Scene, vec3::Vec3, Camera, HittableList, Sphere,
+//! };
+//!
+//! let mut world = HittableList::default();
+//! world.add(Box::new(Sphere {
+//!     center: Vec3::new(0.0, 0.0, -1.0),
+//!     radius: 0.5,
+//!     material: Rc::new(Box::new(Lambertian {
+//!         albedo: Vec3::new(0.5, 0.5, 0.5),
+//!     })),
+//! }));
+//! let scene = Scene::new(world);
+//!
+//! let mut camera = Camera::initialize();
+//! camera.set_image_size(320, 180);
+//! camera.set_samples_per_pixel(16);
+//! let framebuffer = camera.render(&scene);
+//! framebuffer.write_pfm("sphere.pfm").unwrap();
+//! framebuffer.to_image().write_ppm("sphere.ppm").unwrap();
+//! ```
+//!
+//! Besides the analytic shapes in [`primitives`], scenes can hold meshes loaded with
+//! [`ply`], [`stl`] and [`gltf`], signed distance fields ([`sdf`]), heightfields,
+//! participating media and instanced or animated copies of all of them.
+
+use std::f32::consts::PI;
+
+pub mod aabb;
+mod bvh;
+pub mod camera;
+pub mod checkpoint;
+mod color;
+pub mod csg;
+pub mod distributed;
+pub mod environment;
+pub mod gltf;
+pub mod grid;
+pub mod hdr;
+pub mod heightfield;
+pub mod hittable;
+pub mod image;
+pub mod instance;
+pub mod interval;
+mod json;
+pub mod light;
+pub mod mat4;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+mod microfacet;
+pub mod motion;
+mod onb;
+pub mod ply;
+mod png;
+mod ppm;
+pub mod primitives;
+pub mod principled;
+pub mod ray;
+mod rng;
+mod sampling;
+pub mod scene;
+pub mod sdf;
+pub mod sky;
+pub mod stats;
+pub mod stl;
+pub mod texture;
+pub mod tile;
+pub mod vec3;
+
+pub use camera::{Camera, Progress, Progressive};
+pub use checkpoint::RenderState;
+pub use hittable::{HitRecord, Hittable, HittableList};
+pub use image::{Framebuffer, Image};
+pub use interval::Interval;
+pub(crate) use primitives::hit_sphere;
+pub use primitives::Sphere;
+pub use ray::Ray;
+pub use scene::Scene;
+pub use stats::RenderStats;
+
+// fast approximate square root, one newton step from a bit trick
+#[doc(hidden)]
+#[macro_export]
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+/// Converts an angle for the trigonometric functions.
+pub fn degrees_to_radians(degrees: f32) -> f32 {
+    degrees * PI / 180.0
+}
+
+/// Uniform in [0, 1) from the thread local generator.
+#[inline(always)]
+pub fn random_double() -> f32 {
+    rng::next_f32()
+}
+
+/// Uniform in [min, max).
+#[inline(always)]
+pub fn random_double_lim(min: f32, max: f32) -> f32 {
+    min + (max - min) * rng::next_f32()
+}
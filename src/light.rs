@@ -0,0 +1,483 @@
+use std::f32::consts::PI;
+
+use crate::{interval::Interval, onb::Onb, primitives::Quad, random_double, ray::Ray, vec3::Vec3};
+
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+macro_rules! unit_v {
+    ($v:expr) => {
+        $v / f32_len!($v.length_squared())
+    };
+}
+
+pub struct LightSample {
+    // unit direction from the shading point towards the light
+    pub wi: Vec3<f32>,
+    // incident radiance along wi, before visibility
+    pub li: Vec3<f32>,
+    // solid angle density of picking wi
+    pub pdf: f32,
+    // how far along wi the shadow ray has to be clear
+    pub distance: f32,
+}
+
+// something direct lighting can aim shadow rays at
+pub trait Light {
+    fn sample_li(&self, p: Vec3<f32>) -> Option<LightSample>;
+
+    // density sample_li would have picked wi from p with, 0 if wi misses the light
+    fn pdf_li(&self, p: Vec3<f32>, wi: Vec3<f32>) -> f32;
+
+    // point and parallel lights can only be reached by sample_li, pdf is 1
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    // radiance carried by a ray leaving the scene in direction dir, for lights at infinity
+    fn le(&self, _dir: Vec3<f32>) -> Vec3<f32> {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+}
+
+// veach's power heuristic with beta = 2, weight for the strategy that produced f_pdf
+#[inline(always)]
+pub fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+    if f + g == 0.0 {
+        return 0.0;
+    }
+    f / (f + g)
+}
+
+// spherical area light, sampled uniformly inside the cone it subtends
+pub struct SphereLight {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+    pub emit: Vec3<f32>,
+}
+
+impl SphereLight {
+    // 1 - cos of the cone half angle, or None when p is inside the sphere
+    fn cone(&self, p: Vec3<f32>) -> Option<(f32, f32)> {
+        let dist2 = (self.center - p).length_squared();
+        let r2 = self.radius * self.radius;
+        if dist2 <= r2 {
+            return None;
+        }
+        let sin2_max = r2 / dist2;
+        let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+        // sin2 / (1 + cos) keeps precision for small, distant lights
+        Some((sin2_max / (1.0 + cos_max), dist2))
+    }
+}
+
+impl Light for SphereLight {
+    fn sample_li(&self, p: Vec3<f32>) -> Option<LightSample> {
+        let (one_minus_cos_max, dist2) = self.cone(p)?;
+        let cos_theta = 1.0 - random_double() * one_minus_cos_max;
+        let sin2_theta = (1.0 - cos_theta * cos_theta).max(0.0);
+        let sin_theta = sin2_theta.sqrt();
+        let phi = 2.0 * PI * random_double();
+
+        // the exact distance, the fast one tilts samples off the rim of the cone
+        let d = dist2.sqrt();
+        let frame = Onb::new((self.center - p) / d);
+        let wi = frame.to_world(Vec3::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            cos_theta,
+        ));
+        let distance = d * cos_theta
+            - (self.radius * self.radius - dist2 * sin2_theta)
+                .max(0.0)
+                .sqrt();
+        Some(LightSample {
+            wi,
+            li: self.emit,
+            pdf: 1.0 / (2.0 * PI * one_minus_cos_max),
+            distance,
+        })
+    }
+
+    fn pdf_li(&self, p: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        let Some((one_minus_cos_max, _)) = self.cone(p) else {
+            return 0.0;
+        };
+        let oc = self.center - p;
+        let h = wi.dot(oc);
+        let discriminant =
+            h * h - wi.length_squared() * (oc.length_squared() - self.radius * self.radius);
+        if h <= 0.0 || discriminant < 0.0 {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * one_minus_cos_max)
+    }
+}
+
+// parallelogram area light, sampled uniformly by area
+pub struct QuadLight {
+    pub quad: Quad,
+    pub emit: Vec3<f32>,
+}
+
+impl QuadLight {
+    fn area_to_solid_angle(&self, distance: f32, wi: Vec3<f32>) -> f32 {
+        let cosine = wi.dot(self.quad.normal()).abs();
+        if cosine == 0.0 {
+            return 0.0;
+        }
+        distance * distance / (cosine * self.quad.area())
+    }
+}
+
+impl Light for QuadLight {
+    fn sample_li(&self, p: Vec3<f32>) -> Option<LightSample> {
+        let on_light = self.quad.q + self.quad.u * random_double() + self.quad.v * random_double();
+        let to_light = on_light - p;
+        let distance = f32_len!(to_light.length_squared());
+        let wi = to_light / distance;
+        let pdf = self.area_to_solid_angle(distance, wi);
+        if pdf == 0.0 {
+            return None;
+        }
+        // one sided, like DiffuseLight
+        let li = if wi.dot(self.quad.normal()) < 0.0 {
+            self.emit
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+        Some(LightSample {
+            wi,
+            li,
+            pdf,
+            distance,
+        })
+    }
+
+    fn pdf_li(&self, p: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        let ray_t = Interval {
+            min: 0.0,
+            max: f32::INFINITY,
+        };
+        match self.quad.intersect(&Ray::new(p, wi), &ray_t) {
+            Some((t, _, _)) => self.area_to_solid_angle(t, wi),
+            None => 0.0,
+        }
+    }
+}
+
+// isotropic point light, intensity in radiant intensity units
+pub struct PointLight {
+    pub position: Vec3<f32>,
+    pub intensity: Vec3<f32>,
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, p: Vec3<f32>) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let dist2 = to_light.length_squared();
+        let distance = f32_len!(dist2);
+        Some(LightSample {
+            wi: to_light / distance,
+            li: self.intensity / dist2,
+            pdf: 1.0,
+            distance,
+        })
+    }
+
+    fn pdf_li(&self, _p: Vec3<f32>, _wi: Vec3<f32>) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+// point light restricted to a cone, fading out between the two angles
+pub struct SpotLight {
+    pub position: Vec3<f32>,
+    // unit direction the spot points at
+    pub direction: Vec3<f32>,
+    pub intensity: Vec3<f32>,
+    pub cos_falloff_start: f32,
+    pub cos_falloff_end: f32,
+}
+
+impl SpotLight {
+    // angles are the half angles of the fully lit and the outer cone, in degrees
+    pub fn new(
+        position: Vec3<f32>,
+        target: Vec3<f32>,
+        intensity: Vec3<f32>,
+        falloff_start: f32,
+        total_width: f32,
+    ) -> Self {
+        Self {
+            position,
+            direction: unit_v!(target - position),
+            intensity,
+            cos_falloff_start: falloff_start.to_radians().cos(),
+            cos_falloff_end: total_width.to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, w: Vec3<f32>) -> f32 {
+        let cos_theta = w.dot(self.direction);
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_falloff_end {
+            return 0.0;
+        }
+        // smoothstep between the two cones
+        let t =
+            (cos_theta - self.cos_falloff_end) / (self.cos_falloff_start - self.cos_falloff_end);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, p: Vec3<f32>) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let dist2 = to_light.length_squared();
+        let distance = f32_len!(dist2);
+        let wi = to_light / distance;
+        let falloff = self.falloff(-wi);
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi,
+            li: self.intensity * (falloff / dist2),
+            pdf: 1.0,
+            distance,
+        })
+    }
+
+    fn pdf_li(&self, _p: Vec3<f32>, _wi: Vec3<f32>) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+// distant light like the sun, irradiance arrives from a small cone around to_light.
+// with an angular diameter of 0 it is a perfectly parallel delta light
+pub struct DirectionalLight {
+    // unit direction from the scene towards the light
+    pub to_light: Vec3<f32>,
+    pub irradiance: Vec3<f32>,
+    cos_max: f32,
+    one_minus_cos_max: f32,
+}
+
+impl DirectionalLight {
+    // angular diameter in degrees, the sun is about 0.53
+    pub fn new(to_light: Vec3<f32>, irradiance: Vec3<f32>, angular_diameter: f32) -> Self {
+        let half_angle = angular_diameter.to_radians() * 0.5;
+        Self {
+            to_light: to_light / to_light.length_squared().sqrt(),
+            irradiance,
+            cos_max: half_angle.cos(),
+            // 1 - cos(x) = 2 sin^2(x / 2), the sun's cone is too small for the direct form in f32
+            one_minus_cos_max: 2.0 * (half_angle * 0.5).sin().powi(2),
+        }
+    }
+
+    fn solid_angle(&self) -> f32 {
+        2.0 * PI * self.one_minus_cos_max
+    }
+
+    // the cone is far narrower than the error of the fast normalization
+    fn in_cone(&self, dir: Vec3<f32>) -> bool {
+        dir.dot(self.to_light) >= self.cos_max * dir.length_squared().sqrt()
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _p: Vec3<f32>) -> Option<LightSample> {
+        if self.is_delta() {
+            return Some(LightSample {
+                wi: self.to_light,
+                li: self.irradiance,
+                pdf: 1.0,
+                distance: f32::INFINITY,
+            });
+        }
+        let cos_theta = 1.0 - random_double() * self.one_minus_cos_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        let wi = Onb::new(self.to_light).to_world(Vec3::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            cos_theta,
+        ));
+        Some(LightSample {
+            wi,
+            li: self.irradiance / self.solid_angle(),
+            pdf: 1.0 / self.solid_angle(),
+            distance: f32::INFINITY,
+        })
+    }
+
+    fn pdf_li(&self, _p: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        if self.is_delta() || !self.in_cone(wi) {
+            return 0.0;
+        }
+        1.0 / self.solid_angle()
+    }
+
+    fn is_delta(&self) -> bool {
+        self.one_minus_cos_max == 0.0
+    }
+
+    fn le(&self, dir: Vec3<f32>) -> Vec3<f32> {
+        if self.is_delta() || !self.in_cone(dir) {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        self.irradiance / self.solid_angle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DiffuseLight;
+    use std::rc::Rc;
+
+    // midpoint rule over the sphere, uniform in z and phi
+    fn sphere(f: impl Fn(Vec3<f32>) -> f64) -> f64 {
+        let n = 1000;
+        let mut sum = 0.0;
+        for i in 0..n {
+            let z = 2.0 * (i as f32 + 0.5) / n as f32 - 1.0;
+            let r = (1.0 - z * z).sqrt();
+            for j in 0..n {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+                sum += f(Vec3::new(r * phi.cos(), r * phi.sin(), z));
+            }
+        }
+        sum * 4.0 * std::f64::consts::PI / (n * n) as f64
+    }
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        for (f, g) in [(1.0, 1.0), (3.0, 1.0), (0.2, 7.0), (5.0, 0.0)] {
+            let sum = power_heuristic(f, g) + power_heuristic(g, f);
+            assert!((sum - 1.0).abs() < 1e-6, "{f} {g}");
+        }
+        assert_eq!(power_heuristic(1.0, 1.0), 0.5);
+        assert_eq!(power_heuristic(3.0, 1.0), 0.9);
+        // neither strategy can produce the direction
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn sphere_light_samples_land_on_it() {
+        let light = SphereLight {
+            center: Vec3::new(0.3, -0.2, 2.0),
+            radius: 1.0,
+            emit: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..10_000 {
+            let s = light.sample_li(p).unwrap();
+            assert!((s.pdf - light.pdf_li(p, s.wi)).abs() < 1e-3 * s.pdf);
+            let on_light = p + s.wi * s.distance;
+            assert!(((on_light - light.center).length() - light.radius).abs() < 1e-2);
+        }
+        let total = sphere(|wi| light.pdf_li(p, wi) as f64);
+        assert!((total - 1.0).abs() < 1e-2, "{total}");
+        // no cone to sample from inside the light
+        assert!(light.sample_li(light.center).is_none());
+    }
+
+    #[test]
+    fn quad_light_samples_match_its_density() {
+        let emit = Vec3::new(1.0, 1.0, 1.0);
+        // 2 x 2 at z = 1, emitting down towards the origin
+        let light = QuadLight {
+            quad: Quad::new(
+                Vec3::new(-1.0, 1.0, 1.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, -2.0, 0.0),
+                Rc::new(Box::new(DiffuseLight { emit })),
+            ),
+            emit,
+        };
+        let p = Vec3::new(0.3, 0.2, 0.0);
+        for _ in 0..10_000 {
+            let s = light.sample_li(p).unwrap();
+            let pdf = light.pdf_li(p, s.wi);
+            assert!((s.pdf - pdf).abs() < 1e-2 * pdf, "{} {pdf}", s.pdf);
+            assert_eq!(s.li.x(), 1.0);
+            assert!(((p + s.wi * s.distance).z() - 1.0).abs() < 1e-2);
+        }
+        let total = sphere(|wi| light.pdf_li(p, wi) as f64);
+        assert!((total - 1.0).abs() < 1e-2, "{total}");
+        // one sided
+        let s = light.sample_li(Vec3::new(0.0, 0.0, 2.0)).unwrap();
+        assert_eq!(s.li.x(), 0.0);
+    }
+
+    #[test]
+    fn directional_light_cone_integrates_to_one() {
+        let irradiance = Vec3::new(2.0, 2.0, 2.0);
+        let light = DirectionalLight::new(Vec3::new(0.0, 1.0, 1.0), irradiance, 60.0);
+        let p = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..10_000 {
+            let s = light.sample_li(p).unwrap();
+            assert!(s.wi.dot(light.to_light) >= (30.0f32).to_radians().cos() - 1e-3);
+            assert!((s.pdf - light.pdf_li(p, s.wi)).abs() < 1e-3 * s.pdf);
+            assert_eq!(s.li.x(), light.le(s.wi).x());
+        }
+        let total = sphere(|wi| light.pdf_li(p, wi) as f64);
+        assert!((total - 1.0).abs() < 1e-2, "{total}");
+        // le integrates to the irradiance over the cone
+        let e = sphere(|wi| light.le(wi).x() as f64);
+        assert!((e - 2.0).abs() < 2e-2, "{e}");
+
+        // the sun is small but not a delta, a zero diameter is
+        assert!(!DirectionalLight::new(light.to_light, irradiance, 0.53).is_delta());
+        let parallel = DirectionalLight::new(light.to_light, irradiance, 0.0);
+        assert!(parallel.is_delta());
+        let s = parallel.sample_li(p).unwrap();
+        assert_eq!((s.pdf, s.li.x()), (1.0, 2.0));
+        assert_eq!(parallel.pdf_li(p, s.wi), 0.0);
+    }
+
+    #[test]
+    fn point_and_spot_lights_fall_off() {
+        let intensity = Vec3::new(4.0, 4.0, 4.0);
+        let position = Vec3::new(0.0, 0.0, 2.0);
+        let point = PointLight {
+            position,
+            intensity,
+        };
+        let s = point.sample_li(Vec3::new(0.0, 0.0, 0.0)).unwrap();
+        assert!((s.li.x() - 1.0).abs() < 1e-2 && (s.distance - 2.0).abs() < 1e-2);
+        assert!(point.is_delta() && point.pdf_li(Vec3::new(0.0, 0.0, 0.0), s.wi) == 0.0);
+
+        // pointing straight down, fully lit within 20 degrees and dark beyond 40
+        let spot = SpotLight::new(position, Vec3::new(0.0, 0.0, 0.0), intensity, 20.0, 40.0);
+        let li = |x: f32| spot.sample_li(Vec3::new(x, 0.0, 0.0)).map(|s| s.li.x());
+        let d2 = |x: f32| x * x + 4.0;
+        assert!((li(0.0).unwrap() - 1.0).abs() < 1e-2);
+        assert!((li(0.5).unwrap() - 4.0 / d2(0.5)).abs() < 1e-2);
+        assert!(li(2.0).is_none());
+        // tan 30 degrees, in between the cones
+        let mid = li(2.0 * 0.577).unwrap() * d2(2.0 * 0.577) / 4.0;
+        assert!(mid > 0.0 && mid < 1.0, "{mid}");
+    }
+}
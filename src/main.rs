@@ -1,225 +1,1023 @@
-#![feature(strict_provenance)]
-mod color;
-use rand::Rng;
+use std::collections::HashMap;
 use std::f32::consts::PI;
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use color::*;
-mod ppm;
-use ppm::*;
-mod ray;
-use ray::*;
-use vec3::Vec3;
-mod camera;
-use camera::*;
-mod interval;
-mod vec3;
-use interval::*;
-mod material;
-use material::*;
-
-pub struct HitRecord {
-    pub p: Vec3<f32>,
-    pub normal: Vec3<f32>,
-    pub t: f32,
-    pub front_face: bool,
-    pub material: Option<Rc<Box<dyn Material>>>,
-}
-
-impl Default for HitRecord {
-    fn default() -> Self {
-        Self {
-            p: Vec3::default(),
-            normal: Vec3::default(),
-            t: 0.0,
-            front_face: false,
-            material: None,
-        }
+use raytracer::distributed::{serve, Worker};
+use raytracer::{
+    csg::*, environment::*, gltf, grid::*, heightfield::*, instance::*, mat4::*, material::*,
+    medium::*, mesh::*, motion::*, ply, primitives::*, principled::*, scene::*, sdf::*, sky::*,
+    stl, texture::*, tile::*, vec3::Vec3, Camera, Framebuffer, Hittable, HittableList, Progress,
+    Progressive, RenderState,
+};
+
+fn default_scene() -> HittableList {
+    let ground = Lambertian {
+        albedo: Vec3::new(0.8, 0.8, 0.0),
+    };
+    let center = Lambertian {
+        albedo: Vec3::new(0.1, 0.2, 0.5),
+    };
+    let left = RoughDielectric::new(1.5, 0.2);
+    let right = Conductor::gold(0.4);
+    HittableList {
+        objects: vec![
+            Box::new(Sphere {
+                center: Vec3::new(0.0, 0.0, -1.0),
+                radius: 0.5,
+                material: Rc::new(Box::new(center)),
+            }),
+            Box::new(Plane::new(
+                Vec3::new(0.0, -0.5, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Rc::new(Box::new(ground)),
+            )),
+            Box::new(Sphere {
+                center: Vec3::new(-1.0, 0.0, -1.0),
+                radius: 0.5,
+                material: Rc::new(Box::new(left)),
+            }),
+            Box::new(Sphere {
+                center: Vec3::new(1.0, 0.0, -1.0),
+                radius: 0.5,
+                material: Rc::new(Box::new(right)),
+            }),
+        ],
     }
 }
 
-impl HitRecord {
-    pub fn set_face_normal(&mut self, r: &Ray<f32>, outward_normal: Vec3<f32>) {
-        self.front_face = r.direction().dot(outward_normal) < 0.0;
-        self.normal = if self.front_face {
-            outward_normal
-        } else {
-            -outward_normal
+// rows of spheres sweeping the principled parameters one at a time
+fn principled_gallery() -> Scene {
+    let floor = Principled {
+        roughness: SolidColor::scalar(0.9),
+        ..Principled::new(Checker::shared(
+            0.5,
+            SolidColor::shared(Vec3::new(0.2, 0.2, 0.2)),
+            SolidColor::shared(Vec3::new(0.8, 0.8, 0.8)),
+        ))
+    };
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(floor)),
+        ))],
+    };
+
+    let red = Vec3::new(0.8, 0.1, 0.1);
+    let gold = Vec3::new(1.0, 0.76, 0.33);
+    let rows: [Box<dyn Fn(f32) -> Principled>; 4] = [
+        Box::new(|t| Principled {
+            roughness: SolidColor::scalar(t),
+            ..Principled::new(SolidColor::shared(red))
+        }),
+        Box::new(|t| Principled {
+            metallic: SolidColor::scalar(1.0),
+            roughness: SolidColor::scalar(t),
+            ..Principled::new(SolidColor::shared(gold))
+        }),
+        Box::new(|t| Principled {
+            roughness: SolidColor::scalar(0.6),
+            clearcoat: SolidColor::scalar(1.0),
+            clearcoat_gloss: SolidColor::scalar(1.0 - t),
+            sheen: SolidColor::scalar(t),
+            ..Principled::new(SolidColor::shared(red))
+        }),
+        Box::new(|t| Principled {
+            roughness: SolidColor::scalar(t * 0.5),
+            transmission: SolidColor::scalar(1.0),
+            ..Principled::new(SolidColor::shared(Vec3::new(0.9, 1.0, 0.9)))
+        }),
+    ];
+    for (row, material) in rows.iter().enumerate() {
+        for column in 0..5 {
+            let t = column as f32 / 4.0;
+            world.add(Box::new(Sphere {
+                center: Vec3::new(
+                    -2.0 + column as f32,
+                    1.6 - 0.7 * row as f32,
+                    -3.0 - 0.5 * row as f32,
+                ),
+                radius: 0.3,
+                material: Rc::new(Box::new(material(t))),
+            }));
         }
     }
+    let mut scene = Scene::new(world);
+    scene.add_sphere_light(Vec3::new(1.5, 3.5, -1.5), 0.4, Vec3::new(12.0, 12.0, 12.0));
+    scene
 }
 
-trait Hittable {
-    fn hit(
-        &self,
-        r: &Ray<f32>,
-        ray_t: Interval,
-        rec: &mut HitRecord,
-        material: Rc<Box<dyn Material>>,
-    ) -> bool;
-    fn material(&self) -> Rc<Box<dyn Material>>;
-}
-
-struct Sphere {
-    pub center: Vec3<f32>,
-    pub radius: f32,
-    pub material: Rc<Box<dyn Material>>,
-}
-
-#[macro_export]
-macro_rules! f32_len {
-    ($v:expr) => {{
-        let mut i: i32 = $v.to_bits() as i32;
-        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
-        let y = f32::from_bits(i as u32);
-        (((y * y) + $v) / (y)) * 0.5
-    }};
-}
-
-impl Hittable for Sphere {
-    fn hit(
-        &self,
-        r: &Ray<f32>,
-        ray_t: Interval,
-        rec: &mut HitRecord,
-        material: Rc<Box<dyn Material>>,
-    ) -> bool {
-        let oc = self.center - r.origin();
-        let a = r.direction().length_squared();
-        let h = r.direction().dot(oc);
-        let c = oc.length_squared() - self.radius * self.radius;
-        let discriminant = h * h - a * c;
-        if discriminant < 0.0 {
-            return false;
-        }
+// one of each light type over a small set of spheres, no sky
+fn light_gallery() -> Scene {
+    let floor = Lambertian {
+        albedo: Vec3::new(0.6, 0.6, 0.6),
+    };
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(floor)),
+        ))],
+    };
+    for (x, material) in [
+        (
+            -1.2,
+            Rc::new(Box::new(Conductor::copper(0.3)) as Box<dyn Material>),
+        ),
+        (
+            0.0,
+            Rc::new(Box::new(Lambertian {
+                albedo: Vec3::new(0.7, 0.7, 0.7),
+            }) as Box<dyn Material>),
+        ),
+        (
+            1.2,
+            Rc::new(Box::new(Conductor::aluminium(0.2)) as Box<dyn Material>),
+        ),
+    ] {
+        world.add(Box::new(Sphere {
+            center: Vec3::new(x, 0.0, -2.0),
+            radius: 0.5,
+            material,
+        }));
+    }
 
-        let sqrtd = f32_len!(discriminant);
-        let mut root = (h - sqrtd) / a;
-        if !ray_t.surrounds(root) {
-            root = (h + sqrtd) / a;
-            if !ray_t.surrounds(root) {
-                return false;
-            }
-        }
+    let mut scene = Scene::new(world);
+    scene.background = Background::Solid(Vec3::new(0.01, 0.01, 0.02));
+    scene.add_point_light(Vec3::new(-2.0, 1.5, -1.0), Vec3::new(3.0, 2.0, 1.0));
+    scene.add_spot_light(
+        Vec3::new(0.0, 2.5, -2.0),
+        Vec3::new(0.0, 0.0, -2.0),
+        Vec3::new(2.0, 2.0, 6.0),
+        15.0,
+        25.0,
+    );
+    scene.add_directional_light(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.3, 0.3, 0.25), 0.53);
+    scene.add_quad_light(
+        Vec3::new(1.0, 1.5, -2.5),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(4.0, 4.0, 4.0),
+    );
+    scene
+}
 
-        let p = r.at(root);
-        *rec = HitRecord {
-            p,
-            normal: (p - self.center) / self.radius,
-            t: root,
-            front_face: false,
-            material: Some(material),
-        };
-        rec.set_face_normal(r, rec.normal);
-        true
+// the default spheres lit only by an environment map
+fn environment_scene(path: &str, rotation: f32, intensity: f32) -> Scene {
+    let mut scene = Scene::new(default_scene());
+    scene.set_environment(
+        EnvironmentLight::load(path, rotation, intensity).expect("failed to load environment map"),
+    );
+    scene
+}
+
+// smoke, a subsurface-looking blob and thin fog lit by a spot light
+fn media_scene() -> Scene {
+    let floor = Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5),
+    };
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(floor)),
+        ))],
+    };
+    let sphere = |center, radius| {
+        Box::new(Sphere {
+            center,
+            radius,
+            material: Rc::new(Box::new(Lambertian::default()) as Box<dyn Material>),
+        })
+    };
+    world.add(Box::new(ConstantMedium::new(
+        sphere(Vec3::new(-1.1, 0.0, -2.0), 0.5),
+        4.0,
+        Box::new(Isotropic {
+            albedo: Vec3::new(0.8, 0.8, 0.8),
+        }),
+    )));
+    // glass shell around a dense forward scattering medium
+    world.add(Box::new(Sphere {
+        center: Vec3::new(1.1, 0.0, -2.0),
+        radius: 0.5,
+        material: Rc::new(Box::new(RoughDielectric::new(1.4, 0.0))),
+    }));
+    world.add(Box::new(ConstantMedium::new(
+        sphere(Vec3::new(1.1, 0.0, -2.0), 0.49),
+        20.0,
+        Box::new(HenyeyGreenstein {
+            albedo: Vec3::new(0.95, 0.6, 0.4),
+            g: 0.7,
+        }),
+    )));
+    world.add(Box::new(ConstantMedium::new(
+        sphere(Vec3::new(0.0, 0.0, -2.0), 3.0),
+        0.15,
+        Box::new(HenyeyGreenstein {
+            albedo: Vec3::new(0.9, 0.9, 0.9),
+            g: 0.5,
+        }),
+    )));
+
+    let mut scene = Scene::new(world);
+    scene.background = Background::Solid(Vec3::new(0.02, 0.02, 0.03));
+    scene.add_spot_light(
+        Vec3::new(0.0, 2.5, -1.5),
+        Vec3::new(0.0, 0.0, -2.0),
+        Vec3::new(8.0, 8.0, 8.0),
+        20.0,
+        30.0,
+    );
+    scene.add_sphere_light(Vec3::new(-2.0, 1.5, -1.0), 0.2, Vec3::new(15.0, 12.0, 9.0));
+    scene
+}
+
+// a density grid over the floor, lit from behind so the thin edges glow
+fn grid_scene(path: &str, resolution: Option<(usize, usize, usize)>, density: f32) -> Scene {
+    let floor = Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5),
+    };
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(floor)),
+        ))],
+    };
+    let grid = VoxelGrid::load(path, resolution).expect("failed to load density grid");
+    world.add(Box::new(GridMedium::new(
+        grid,
+        Vec3::new(-1.0, -0.5, -3.0),
+        Vec3::new(1.0, 0.9, -1.5),
+        density,
+        Box::new(HenyeyGreenstein {
+            albedo: Vec3::new(0.9, 0.9, 0.9),
+            g: 0.6,
+        }),
+    )));
+
+    let mut scene = Scene::new(world);
+    scene.background = Background::Solid(Vec3::new(0.05, 0.06, 0.09));
+    scene.add_directional_light(Vec3::new(-0.4, 0.6, -1.0), Vec3::new(3.0, 2.8, 2.5), 0.53);
+    scene.add_sphere_light(Vec3::new(2.0, 1.5, -0.5), 0.3, Vec3::new(6.0, 7.0, 9.0));
+    scene
+}
+
+// one sphere and one quad shared by every instance
+fn instance_scene() -> Scene {
+    let floor = Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5),
+    };
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(floor)),
+        ))],
+    };
+    let sphere: Rc<dyn Hittable> = Rc::new(Sphere {
+        center: Vec3::new(0.0, 0.0, 0.0),
+        radius: 1.0,
+        material: Rc::new(Box::new(Conductor::copper(0.3))),
+    });
+    let tile: Rc<dyn Hittable> = Rc::new(Quad::new(
+        Vec3::new(-0.5, -0.5, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Rc::new(Box::new(Lambertian {
+            albedo: Vec3::new(0.2, 0.4, 0.8),
+        })),
+    ));
+
+    // squashed and stretched ellipsoids, normals have to follow the inverse transpose
+    for i in 0..3 {
+        let x = (i as f32 - 1.0) * 0.9;
+        let transform = Mat4::translate(Vec3::new(x, -0.15, -2.2))
+            * Mat4::rotate_z(30.0 * (i as f32 - 1.0))
+            * Mat4::scale(Vec3::new(0.4, 0.2 + 0.1 * i as f32, 0.25));
+        world.add(Box::new(Instance::new(sphere.clone(), transform)));
     }
+    // a ring of tiles turned to face the center, open towards the camera
+    for i in (0..12).filter(|i| !(5..=7).contains(i)) {
+        let angle = 30.0 * i as f32;
+        let transform = Mat4::translate(Vec3::new(0.0, 0.1, -2.2))
+            * Mat4::rotate_y(angle)
+            * Mat4::translate(Vec3::new(0.0, 0.0, -1.6))
+            * Mat4::rotate_x(-10.0)
+            * Mat4::uniform_scale(0.6);
+        world.add(Box::new(Instance::new(tile.clone(), transform)));
+    }
+
+    let mut scene = Scene::new(world);
+    scene.add_sphere_light(Vec3::new(0.0, 1.5, -1.0), 0.3, Vec3::new(20.0, 20.0, 20.0));
+    scene
+}
 
-    fn material(&self) -> Rc<Box<dyn Material>> {
-        self.material.clone()
+// things moving while the shutter is open from t = 0 to 1
+fn motion_scene(cam: &mut Camera) -> Scene {
+    cam.set_shutter(0.0, 1.0);
+    let floor = Checker::shared(
+        0.3,
+        SolidColor::shared(Vec3::new(0.2, 0.3, 0.1)),
+        SolidColor::shared(Vec3::new(0.9, 0.9, 0.9)),
+    );
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(Principled::new(floor))),
+        ))],
+    };
+    for i in 0..4 {
+        let x = (i as f32 - 1.5) * 0.6;
+        let center = Vec3::new(x, -0.3, -2.0 - 0.2 * i as f32);
+        world.add(Box::new(MovingSphere {
+            center0: center,
+            center1: center + Vec3::new(0.0, 0.1 + 0.15 * i as f32, 0.0),
+            time0: 0.0,
+            time1: 1.0,
+            radius: 0.2,
+            material: Rc::new(Box::new(Lambertian {
+                albedo: Vec3::new(0.8, 0.3 + 0.15 * i as f32, 0.2),
+            })),
+        }));
     }
+    // a blade spinning a quarter turn around its middle while drifting sideways
+    let blade: Rc<dyn Hittable> = Rc::new(Quad::new(
+        Vec3::new(-0.5, -0.1, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.2, 0.0),
+        Rc::new(Box::new(Lambertian {
+            albedo: Vec3::new(0.2, 0.3, 0.8),
+        })),
+    ));
+    let axis = Vec3::new(0.0, 0.0, 1.0);
+    let scale = Vec3::new(0.8, 0.8, 0.8);
+    world.add(Box::new(AnimatedInstance::new(
+        blade,
+        Trs::new(
+            Vec3::new(-0.1, 0.35, -2.0),
+            Quat::from_axis_angle(axis, 0.0),
+            scale,
+        ),
+        Trs::new(
+            Vec3::new(0.1, 0.35, -2.0),
+            Quat::from_axis_angle(axis, 90.0),
+            scale,
+        ),
+        0.0,
+        1.0,
+    )));
+
+    let mut scene = Scene::new(world);
+    scene.add_sphere_light(Vec3::new(1.0, 2.0, -0.5), 0.3, Vec3::new(20.0, 20.0, 20.0));
+    scene
 }
 
-struct HittableList {
-    pub objects: Vec<Box<dyn Hittable>>,
+// one of each analytic primitive on a plane
+fn shapes_scene() -> Scene {
+    let solid = |r: f32, g: f32, b: f32| -> Rc<Box<dyn Material>> {
+        Rc::new(Box::new(Principled {
+            roughness: SolidColor::scalar(0.4),
+            ..Principled::new(SolidColor::shared(Vec3::new(r, g, b)))
+        }))
+    };
+    let checker = Checker::shared(
+        0.25,
+        SolidColor::shared(Vec3::new(0.2, 0.2, 0.2)),
+        SolidColor::shared(Vec3::new(0.8, 0.8, 0.8)),
+    );
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            up,
+            Rc::new(Box::new(Principled::new(checker))),
+        ))],
+    };
+    world.add(Box::new(Disk::new(
+        Vec3::new(-1.5, 0.0, -3.0),
+        Vec3::new(0.3, 0.2, 1.0),
+        0.4,
+        solid(0.8, 0.2, 0.2),
+    )));
+    world.add(Box::new(Quad::new(
+        Vec3::new(-1.0, -0.4, -3.0),
+        Vec3::new(0.6, 0.0, 0.2),
+        Vec3::new(0.0, 0.7, 0.0),
+        solid(0.9, 0.6, 0.1),
+    )));
+    world.add(Box::new(AxisBox::new(
+        Vec3::new(-0.2, -0.5, -3.2),
+        Vec3::new(0.3, 0.0, -2.7),
+        solid(0.2, 0.7, 0.3),
+    )));
+    world.add(Box::new(oriented_box(
+        Vec3::new(0.05, 0.3, -2.95),
+        Vec3::new(0.35, 0.35, 0.35),
+        Quat::from_axis_angle(Vec3::new(1.0, 1.0, 0.0), 40.0),
+        solid(0.2, 0.5, 0.8),
+    )));
+    world.add(Box::new(Cylinder::new(
+        Vec3::new(0.8, -0.5, -3.0),
+        up,
+        0.25,
+        0.7,
+        solid(0.6, 0.3, 0.7),
+    )));
+    world.add(Box::new(Cone::new(
+        Vec3::new(1.5, -0.5, -2.8),
+        up,
+        0.3,
+        0.8,
+        solid(0.9, 0.9, 0.3),
+    )));
+    world.add(Box::new(Torus::new(
+        Vec3::new(-0.6, -0.2, -2.0),
+        Vec3::new(0.0, 1.0, 0.5),
+        0.25,
+        0.08,
+        Rc::new(Box::new(Conductor::gold(0.2))),
+    )));
+    world.add(Box::new(Cylinder::new(
+        Vec3::new(0.6, -0.35, -1.9),
+        Vec3::new(1.0, 0.2, 0.3),
+        0.12,
+        0.4,
+        Rc::new(Box::new(Conductor::copper(0.3))),
+    )));
+
+    let mut scene = Scene::new(world);
+    scene.add_sphere_light(Vec3::new(-1.0, 2.5, -1.0), 0.4, Vec3::new(15.0, 15.0, 15.0));
+    scene
 }
 
-pub fn degrees_to_radians(degrees: f32) -> f32 {
-    degrees * PI / 180.0
+// a lens, a slotted bolt and a sphere with a corner cut out of it
+fn csg_scene() -> Scene {
+    let solid = |r: f32, g: f32, b: f32| -> Rc<Box<dyn Material>> {
+        Rc::new(Box::new(Principled {
+            roughness: SolidColor::scalar(0.4),
+            ..Principled::new(SolidColor::shared(Vec3::new(r, g, b)))
+        }))
+    };
+    let checker = Checker::shared(
+        0.25,
+        SolidColor::shared(Vec3::new(0.2, 0.2, 0.2)),
+        SolidColor::shared(Vec3::new(0.8, 0.8, 0.8)),
+    );
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            up,
+            Rc::new(Box::new(Principled::new(checker))),
+        ))],
+    };
+
+    // biconvex lens where two spheres overlap
+    let glass: Rc<Box<dyn Material>> = Rc::new(Box::new(RoughDielectric::new(1.5, 0.0)));
+    world.add(Box::new(Csg::intersection(
+        Box::new(Sphere {
+            center: Vec3::new(-1.35, 0.0, -2.2),
+            radius: 0.6,
+            material: glass.clone(),
+        }),
+        Box::new(Sphere {
+            center: Vec3::new(-0.65, 0.0, -2.9),
+            radius: 0.6,
+            material: glass,
+        }),
+    )));
+
+    // bolt: shaft and head unioned, then a slot cut across the head
+    let steel: Rc<Box<dyn Material>> = Rc::new(Box::new(Conductor::aluminium(0.3)));
+    let bolt = Csg::union(
+        Box::new(Cylinder::new(
+            Vec3::new(0.0, -0.5, -2.5),
+            up,
+            0.1,
+            0.6,
+            steel.clone(),
+        )),
+        Box::new(Cylinder::new(
+            Vec3::new(0.0, 0.1, -2.5),
+            up,
+            0.25,
+            0.15,
+            steel.clone(),
+        )),
+    );
+    world.add(Box::new(Csg::difference(
+        Box::new(bolt),
+        Box::new(AxisBox::new(
+            Vec3::new(-0.3, 0.18, -2.54),
+            Vec3::new(0.3, 0.3, -2.46),
+            steel,
+        )),
+    )));
+
+    // cutaway, the carved walls take the box's material
+    world.add(Box::new(Csg::difference(
+        Box::new(Sphere {
+            center: Vec3::new(1.0, 0.0, -2.4),
+            radius: 0.5,
+            material: solid(0.8, 0.3, 0.2),
+        }),
+        Box::new(AxisBox::new(
+            Vec3::new(1.0, 0.0, -2.4),
+            Vec3::new(1.6, 0.6, -1.8),
+            solid(0.9, 0.8, 0.5),
+        )),
+    )));
+
+    let mut scene = Scene::new(world);
+    scene.add_sphere_light(Vec3::new(-1.0, 2.5, -1.0), 0.4, Vec3::new(15.0, 15.0, 15.0));
+    scene
 }
 
-#[inline(always)]
-pub fn random_double() -> f32 {
-    let mut rng = rand::thread_rng();
-    let random_number: f32 = rng.gen();
-    random_number
+// distance field shapes: a mandelbulb, a blob, a bumpy ball and a row of tori
+fn sdf_scene() -> Scene {
+    let solid = |r: f32, g: f32, b: f32| -> Rc<Box<dyn Material>> {
+        Rc::new(Box::new(Principled {
+            roughness: SolidColor::scalar(0.4),
+            ..Principled::new(SolidColor::shared(Vec3::new(r, g, b)))
+        }))
+    };
+    let checker = Checker::shared(
+        0.25,
+        SolidColor::shared(Vec3::new(0.2, 0.2, 0.2)),
+        SolidColor::shared(Vec3::new(0.8, 0.8, 0.8)),
+    );
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(Principled::new(checker))),
+        ))],
+    };
+
+    let mut bulb = SdfObject::new(
+        Mandelbulb::shared(Vec3::new(0.0, 0.05, -2.2), 0.45),
+        Rc::new(Box::new(Conductor::gold(0.3))),
+    );
+    bulb.step_scale = 0.9;
+    world.add(Box::new(bulb));
+
+    let blob = SmoothUnion::shared(
+        Rounded::shared(
+            SdfBox::shared(Vec3::new(-1.2, -0.3, -2.4), Vec3::new(0.2, 0.15, 0.2)),
+            0.05,
+        ),
+        SdfSphere::shared(Vec3::new(-1.2, 0.05, -2.4), 0.2),
+        0.2,
+    );
+    world.add(Box::new(SdfObject::new(blob, solid(0.2, 0.5, 0.8))));
+
+    let mut bumpy = SdfObject::new(
+        Displace::shared(
+            SdfSphere::shared(Vec3::new(1.2, -0.1, -2.4), 0.35),
+            0.03,
+            20.0,
+        ),
+        solid(0.8, 0.3, 0.2),
+    );
+    bumpy.step_scale = 0.6;
+    world.add(Box::new(bumpy));
+
+    let tori = Repeat::shared(
+        SdfTorus::shared(Vec3::new(0.0, -0.45, -1.6), 0.08, 0.03),
+        Vec3::new(0.25, 0.0, 0.0),
+        [3, 0, 0],
+    );
+    world.add(Box::new(SdfObject::new(tori, solid(0.3, 0.7, 0.3))));
+
+    let mut scene = Scene::new(world);
+    scene.add_sphere_light(Vec3::new(-1.0, 2.5, -1.0), 0.4, Vec3::new(15.0, 15.0, 15.0));
+    scene
 }
 
-#[inline(always)]
-pub fn random_double_lim(min: f32, max: f32) -> f32 {
-    let mut rng = rand::thread_rng();
-    let random_number: f32 = rng.gen_range(min..max);
-    random_number
+// rolling hills from a few octaves of sines, or from a grayscale image when given one
+fn terrain_scene(path: Option<&str>) -> Scene {
+    let grass: Rc<Box<dyn Material>> = Rc::new(Box::new(Principled {
+        roughness: SolidColor::scalar(0.8),
+        ..Principled::new(SolidColor::shared(Vec3::new(0.35, 0.45, 0.2)))
+    }));
+    let corner = Vec3::new(-8.0, -1.6, -14.0);
+    let extent = Vec3::new(16.0, 2.0, 14.5);
+    let terrain = match path {
+        Some(path) => Heightfield::load(path, corner, extent, grass)
+            .expect("failed to load heightfield image"),
+        None => Heightfield::from_fn(
+            512,
+            512,
+            |u, v| {
+                let (x, z) = (u * 2.0 * PI, v * 2.0 * PI);
+                let mut h = 0.0;
+                let mut amplitude = 0.5;
+                for octave in 0..5 {
+                    let f = (1 << octave) as f32 * 1.7;
+                    h += amplitude
+                        * (0.5 + 0.25 * ((f * x + octave as f32).sin() + (f * z * 1.3).cos()));
+                    amplitude *= 0.45;
+                }
+                h
+            },
+            corner,
+            extent,
+            grass,
+        ),
+    };
+    let world = HittableList {
+        objects: vec![Box::new(terrain)],
+    };
+    let mut scene = Scene::new(world);
+    scene.set_sun_sky(&SunSky::new(20.0, 60.0, 3.0, 0.05));
+    scene
 }
 
-impl HittableList {
-    pub fn add(&mut self, object: Box<dyn Hittable>) {
-        self.objects.push(object);
+// a ply or stl model scaled to fit a unit box standing on the floor, colored by its
+// vertex colors if it has any
+fn mesh_scene(path: &str) -> Scene {
+    let is_stl = path.to_ascii_lowercase().ends_with(".stl");
+    let data = if is_stl {
+        stl::load(path)
+    } else {
+        ply::load(path)
     }
+    .expect("failed to load mesh");
+    let bounds = data.bounding_box();
+    let size = bounds.max() - bounds.min();
+    let scale = 1.0 / size.x().max(size.y()).max(size.z());
+    let bottom_center = Vec3::new(
+        (bounds.x.min + bounds.x.max) * 0.5,
+        bounds.y.min,
+        (bounds.z.min + bounds.z.max) * 0.5,
+    );
+
+    let material = Principled {
+        roughness: SolidColor::scalar(0.5),
+        ..Principled::new(VertexColor::shared(SolidColor::shared(Vec3::new(
+            0.7, 0.7, 0.7,
+        ))))
+    };
+    let mesh = TriangleMesh::new(data, Rc::new(Box::new(material)));
+    println!("{} triangles", mesh.triangle_count());
+    let placed = Instance::new(
+        Rc::new(mesh),
+        Mat4::translate(Vec3::new(0.0, -0.5, -2.0))
+            * Mat4::uniform_scale(scale)
+            * Mat4::translate(-bottom_center),
+    );
 
-    pub fn clear(&mut self) {
-        self.objects.clear();
+    let checker = Checker::shared(
+        0.25,
+        SolidColor::shared(Vec3::new(0.2, 0.2, 0.2)),
+        SolidColor::shared(Vec3::new(0.8, 0.8, 0.8)),
+    );
+    let mut world = HittableList {
+        objects: vec![Box::new(Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Rc::new(Box::new(Principled::new(checker))),
+        ))],
+    };
+    world.add(Box::new(placed));
+    let mut scene = Scene::new(world);
+    scene.add_sphere_light(Vec3::new(-1.0, 2.5, -1.0), 0.4, Vec3::new(15.0, 15.0, 15.0));
+    scene
+}
+
+// a gltf file as is, framed from the front when it brings no camera
+fn gltf_scene(path: &str, cam: &mut Camera) -> Scene {
+    let gltf = gltf::load(path).expect("failed to load gltf");
+    for warning in &gltf.warnings {
+        println!("warning: {warning}");
     }
+    match &gltf.camera {
+        Some(view) => cam.look_at(view.from, view.at, view.up, view.vfov),
+        None => {
+            let b = gltf.bounds;
+            let center = (b.min() + b.max()) * 0.5;
+            let radius = (b.max() - b.min()).length_squared().sqrt() * 0.5;
+            // far enough back for the bounding sphere to fit the view
+            let vfov: f32 = 40.0;
+            let distance = radius / (vfov.to_radians() * 0.5).sin();
+            let direction = Vec3::<f32>::new(0.0, 0.3, 1.0);
+            let from = center + direction * (distance / direction.length_squared().sqrt());
+            cam.look_at(from, center, Vec3::new(0.0, 1.0, 0.0), vfov);
+        }
+    }
+    Scene::new(HittableList {
+        objects: gltf.objects,
+    })
+}
 
-    pub fn hit(&self, r: &Ray<f32>, ray_t: Interval, hit_record: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::default();
-        let mut hit_anything = false;
-        let mut closest_so_far = ray_t.max;
+// the default spheres outdoors
+fn sky_scene(elevation: f32, azimuth: f32, turbidity: f32) -> Scene {
+    let mut scene = Scene::new(default_scene());
+    scene.set_sun_sky(&SunSky::new(elevation, azimuth, turbidity, 0.05));
+    scene
+}
 
-        for object in &self.objects {
-            if object.hit(
-                r,
-                Interval {
-                    min: ray_t.min,
-                    max: closest_so_far,
-                },
-                &mut temp_rec,
-                object.material(),
-            ) {
-                hit_anything = true;
-                closest_so_far = temp_rec.t;
-                hit_record.p = temp_rec.p;
-                hit_record.normal = temp_rec.normal;
-                hit_record.t = temp_rec.t;
-                hit_record.front_face = temp_rec.front_face;
-                hit_record.material = Some(object.material());
+fn progress_bar(p: &Progress) -> String {
+    let rate = format!("{:.2} M rays/s", p.rays_per_second / 1e6);
+    match p.fraction {
+        Some(f) => {
+            let filled = (f * 30.0).round() as usize;
+            let eta = p
+                .eta
+                .map_or(String::new(), |eta| format!("  eta {:.0?}", eta));
+            format!(
+                "[{}{}] {:5.1}%  {rate}{eta}",
+                "#".repeat(filled),
+                "-".repeat(30 - filled),
+                f * 100.0
+            )
+        }
+        // no end in sight, only what has been done
+        None => format!("{} samples  {rate}  {:.0?}", p.samples, p.elapsed),
+    }
+}
+
+// starts this binary as a worker on a free local port, with the same scene arguments
+fn spawn_worker() -> (Child, String) {
+    let mut args = Vec::new();
+    let mut raw = std::env::args().skip(1);
+    while let Some(a) = raw.next() {
+        match a.strip_prefix("--") {
+            // the coordinator's own options
+            Some(
+                "workers" | "connect" | "worker-timeout" | "checkpoint" | "checkpoint-every"
+                | "time" | "stats" | "stats-file",
+            ) => {
+                raw.next();
             }
+            _ => args.push(a),
+        }
+    }
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args(args)
+        .args(["--serve", "127.0.0.1:0"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // the scene may print before the worker says where it listens
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    for line in stdout.lines().map_while(|l| l.ok()) {
+        if let Some(addr) = line.strip_prefix("listening on ") {
+            return (child, addr.to_string());
         }
-        hit_anything
     }
+    child.kill().ok();
+    child.wait().ok();
+    panic!("worker exited before listening");
 }
 
 fn main() {
-    let ground = Lambertian {
-        albedo: Vec3::new(0.8, 0.8, 0.0),
-    };
-    let center = Lambertian {
-        albedo: Vec3::new(0.1, 0.2, 0.5),
-    };
-    let left = Metal {
-        attenuation: Vec3::new(0.8, 0.8, 0.8),
-        fuzz: 0.3,
+    // --name value options may go anywhere, everything else is positional
+    let mut args: Vec<String> = Vec::new();
+    let mut options: HashMap<String, String> = HashMap::new();
+    let mut raw = std::env::args();
+    while let Some(a) = raw.next() {
+        match a.strip_prefix("--") {
+            Some(name) => {
+                let value = raw
+                    .next()
+                    .unwrap_or_else(|| panic!("--{name} needs a value"));
+                options.insert(name.to_string(), value);
+            }
+            None => args.push(a),
+        }
+    }
+    let option = |name: &str| {
+        options.get(name).map(|v| {
+            v.parse::<f64>()
+                .unwrap_or_else(|_| panic!("--{name} expects a number"))
+        })
     };
-    let right = Metal {
-        attenuation: Vec3::new(0.8, 0.6, 0.2),
-        fuzz: 1.0,
+
+    let arg = |i: usize, default: f32| {
+        args.get(i)
+            .and_then(|a| a.parse::<f32>().ok())
+            .unwrap_or(default)
     };
-    let mut world = HittableList {
-        objects: vec![
-            Box::new(Sphere {
-                center: Vec3::new(0.0, 0.0, -1.0),
-                radius: 0.5,
-                material: Rc::new(Box::new(center)),
-            }),
-            Box::new(Sphere {
-                center: Vec3::new(0.0, -100.5, -1.0),
-                radius: 100.0,
-                material: Rc::new(Box::new(ground)),
-            }),
-            Box::new(Sphere {
-                center: Vec3::new(-1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: Rc::new(Box::new(left)),
-            }),
-            Box::new(Sphere {
-                center: Vec3::new(1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: Rc::new(Box::new(right)),
-            }),
-        ],
+    let mut cam = Camera::initialize();
+    let scene = match args.get(1).map(|a| a.as_str()) {
+        Some("principled") => principled_gallery(),
+        Some("lights") => light_gallery(),
+        Some("media") => media_scene(),
+        Some("instances") => instance_scene(),
+        Some("motion") => motion_scene(&mut cam),
+        Some("shapes") => shapes_scene(),
+        Some("csg") => csg_scene(),
+        Some("sdf") => sdf_scene(),
+        // mesh <model.ply|model.stl>
+        Some("mesh") => mesh_scene(args.get(2).expect("usage: mesh <model.ply|model.stl>")),
+        // gltf <scene.gltf|scene.glb>
+        Some("gltf") => gltf_scene(
+            args.get(2).expect("usage: gltf <scene.gltf|scene.glb>"),
+            &mut cam,
+        ),
+        // terrain [heightmap.pgm|heightmap.pfm]
+        Some("terrain") => terrain_scene(args.get(2).map(|a| a.as_str())),
+        // grid <density.vol> [density scale] or grid <density.raw> <nx> <ny> <nz> [density scale]
+        Some("grid") => {
+            let path = args
+                .get(2)
+                .expect("usage: grid <file.vol|file.raw nx ny nz> [density]");
+            let dim = |i: usize| args.get(i).and_then(|a| a.parse::<usize>().ok());
+            match (dim(3), dim(4), dim(5)) {
+                (Some(nx), Some(ny), Some(nz)) => {
+                    grid_scene(path, Some((nx, ny, nz)), arg(6, 10.0))
+                }
+                _ => grid_scene(path, None, arg(3, 10.0)),
+            }
+        }
+        // env <map.hdr|map.pfm> [rotation degrees] [intensity]
+        Some("env") => environment_scene(
+            args.get(2)
+                .expect("usage: env <map.hdr> [rotation] [intensity]"),
+            arg(3, 0.0),
+            arg(4, 1.0),
+        ),
+        // sky [elevation] [azimuth] [turbidity]
+        Some("sky") => sky_scene(arg(2, 35.0), arg(3, 60.0), arg(4, 3.0)),
+        _ => Scene::new(default_scene()),
     };
 
-    let cam = Camera::initialize();
-    cam.render(&mut world);
+    // --shutter <open,close> overrides the interval the scene moves over
+    if let Some(shutter) = options.get("shutter") {
+        let v: Vec<f32> = shutter
+            .split(',')
+            .map(|t| t.trim().parse().expect("--shutter expects open,close"))
+            .collect();
+        let [open, close] = v[..] else {
+            panic!("--shutter expects open,close");
+        };
+        cam.set_shutter(open, close);
+    }
+    // --spp <samples per pixel>, --pass <samples per pass> and --time <seconds>, the
+    // latter two render progressively and keep preview.ppm up to date. --checkpoint <file>
+    // saves the render there every --checkpoint-every seconds and resumes from it if it
+    // exists, --seed <n> picks the random numbers
+    if let Some(spp) = option("spp") {
+        cam.set_samples_per_pixel(spp as usize);
+    }
+    if let Some(seed) = option("seed") {
+        cam.set_seed(seed as u64);
+    }
+    // --tile <size> and --order <scanline|spiral|hilbert> pick the tiling, --crop
+    // <x,y,width,height> renders only that window of the image
+    if option("tile").is_some() || options.contains_key("order") {
+        let order = match options.get("order").map(|o| o.as_str()) {
+            None | Some("hilbert") => TileOrder::Hilbert,
+            Some("spiral") => TileOrder::Spiral,
+            Some("scanline") => TileOrder::Scanline,
+            Some(o) => panic!("unknown tile order {o}, expected scanline, spiral or hilbert"),
+        };
+        cam.set_tiles(option("tile").map_or(32, |n| n as usize), order);
+    }
+    if let Some(crop) = options.get("crop") {
+        let v: Vec<usize> = crop
+            .split(',')
+            .map(|n| n.trim().parse().expect("--crop expects x,y,width,height"))
+            .collect();
+        let [x, y, width, height] = v[..] else {
+            panic!("--crop expects x,y,width,height");
+        };
+        cam.set_crop(Some(Rect::new(x, y, width, height)));
+    }
+    // --serve <addr> turns this process into a render worker for the scene it was given,
+    // --workers <n> spawns that many local workers and --connect <addr,addr,..> uses
+    // running ones, all of them need the same scene arguments
+    if let Some(addr) = options.get("serve") {
+        let listener = TcpListener::bind(addr).unwrap();
+        println!("listening on {}", listener.local_addr().unwrap());
+        std::io::stdout().flush().unwrap();
+        for stream in listener.incoming() {
+            if let Err(e) = stream.and_then(|s| serve(s, &cam, &scene)) {
+                eprintln!("worker: {e}");
+            }
+        }
+        return;
+    }
+    // a progress bar on stderr when that is a terminal, redrawn ten times a second
+    let terminal = std::io::stderr().is_terminal();
+    if terminal {
+        let mut last_draw: Option<Instant> = None;
+        cam.set_progress(move |p| {
+            if last_draw.is_some_and(|t| t.elapsed() < Duration::from_millis(100)) {
+                return;
+            }
+            last_draw = Some(Instant::now());
+            eprint!("\r\x1b[2K{}", progress_bar(p));
+        });
+    }
+    let mut children = Vec::new();
+    let mut workers = Vec::new();
+    for _ in 0..option("workers").map_or(0, |n| n as usize) {
+        let (child, addr) = spawn_worker();
+        children.push(child);
+        workers.push(Worker::connect(&addr).unwrap());
+    }
+    for addr in options
+        .get("connect")
+        .into_iter()
+        .flat_map(|a| a.split(','))
+    {
+        workers.push(Worker::connect(addr.trim()).unwrap());
+    }
+    // --worker-timeout <seconds> drops workers that take longer than that on a tile
+    if let Some(timeout) = option("worker-timeout") {
+        for worker in &mut workers {
+            worker
+                .set_timeout(Duration::from_secs_f64(timeout))
+                .unwrap();
+        }
+    }
+
+    let checkpoint = options.get("checkpoint").cloned();
+    let endless = option("pass").is_some() || option("time").is_some();
+    let framebuffer = if endless || checkpoint.is_some() || !workers.is_empty() {
+        let settings = Progressive {
+            samples_per_pass: option("pass").map_or(4, |n| n as usize),
+            // without a sample count, --pass and --time go on until stopped
+            target_samples: option("spp").map_or(
+                if endless {
+                    usize::MAX
+                } else {
+                    cam.samples_per_pixel()
+                },
+                |n| n as usize,
+            ),
+            time_budget: option("time").map(Duration::from_secs_f64),
+            preview_interval: 1,
+            checkpoint_interval: option("checkpoint-every")
+                .map_or(Duration::from_secs(60), Duration::from_secs_f64),
+            checkpoint,
+        };
+        let state = match &settings.checkpoint {
+            Some(path) if Path::new(path).exists() => {
+                let state = RenderState::load(path).unwrap();
+                println!(
+                    "resuming {path} at {} samples per pixel",
+                    state.min_samples()
+                );
+                state
+            }
+            _ => cam.render_state(),
+        };
+        let preview = |preview: &Framebuffer| {
+            if terminal {
+                eprint!("\r\x1b[2K");
+            }
+            println!("{} samples per pixel", preview.metadata.samples_per_pixel);
+            preview.to_image().overwrite_ppm("preview.ppm").unwrap();
+        };
+        if workers.is_empty() {
+            cam.resume(&scene, state, &settings, preview).unwrap()
+        } else {
+            let started = workers.len();
+            let framebuffer = cam
+                .render_distributed(state, &settings, &mut workers, preview)
+                .unwrap();
+            if workers.len() < started {
+                println!("{} of {started} workers failed", started - workers.len());
+            }
+            framebuffer
+        }
+    } else {
+        cam.render(&scene)
+    };
+    if terminal {
+        eprint!("\r\x1b[2K");
+    }
+    drop(workers);
+    for mut child in children {
+        child.kill().ok();
+        child.wait().ok();
+    }
+    println!(
+        "rendered {} samples per pixel in {:.1?}",
+        framebuffer.metadata.samples_per_pixel, framebuffer.metadata.render_time
+    );
+    // --stats summary prints what the render did, --stats-file <file.json> writes the same
+    // as a json report of its own
+    match options.get("stats").map(|s| s.as_str()) {
+        None => {}
+        Some("summary") => println!("{}", framebuffer.metadata.stats),
+        Some(s) => panic!("unknown --stats {s}, expected summary"),
+    }
+    if let Some(path) = options.get("stats-file") {
+        std::fs::write(path, framebuffer.metadata.stats.to_json() + "\n").unwrap();
+    }
+    framebuffer.to_image().write_ppm("out.ppm").unwrap();
 
     println!("Hello, world!");
 }
@@ -0,0 +1,349 @@
+use std::ops::Mul;
+
+use crate::vec3::Vec3;
+
+// row major affine transform, points are column vectors so a * b applies b first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4(pub [[f32; 4]; 4]);
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub fn translate(offset: Vec3<f32>) -> Self {
+        let mut m = Self::IDENTITY;
+        m.0[0][3] = offset.x();
+        m.0[1][3] = offset.y();
+        m.0[2][3] = offset.z();
+        m
+    }
+
+    pub fn scale(s: Vec3<f32>) -> Self {
+        let mut m = Self::IDENTITY;
+        m.0[0][0] = s.x();
+        m.0[1][1] = s.y();
+        m.0[2][2] = s.z();
+        m
+    }
+
+    pub fn uniform_scale(s: f32) -> Self {
+        Self::scale(Vec3::new(s, s, s))
+    }
+
+    // counter clockwise looking down the axis towards the origin
+    pub fn rotate(axis: Vec3<f32>, degrees: f32) -> Self {
+        let a = axis / axis.length_squared().sqrt();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let t = 1.0 - cos;
+        Mat4([
+            [
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.0,
+            ],
+            [
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.0,
+            ],
+            [
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotate_x(degrees: f32) -> Self {
+        Self::rotate(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotate_y(degrees: f32) -> Self {
+        Self::rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotate_z(degrees: f32) -> Self {
+        Self::rotate(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    // gauss-jordan with partial pivoting, None if the matrix is singular
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.0;
+        let mut inv = Self::IDENTITY.0;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let d = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= d;
+                inv[col][j] *= d;
+            }
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let f = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+        Some(Mat4(inv))
+    }
+
+    pub fn transform_point(&self, p: Vec3<f32>) -> Vec3<f32> {
+        let m = &self.0;
+        let row = |i: usize| m[i][0] * p.x() + m[i][1] * p.y() + m[i][2] * p.z() + m[i][3];
+        let w = row(3);
+        if w == 1.0 {
+            Vec3::new(row(0), row(1), row(2))
+        } else {
+            Vec3::new(row(0), row(1), row(2)) / w
+        }
+    }
+
+    // ignores the translation
+    pub fn transform_vector(&self, v: Vec3<f32>) -> Vec3<f32> {
+        let m = &self.0;
+        let row = |i: usize| m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    // normals go through the inverse transpose, so this takes the inverse of the
+    // transform the surface went through. the result isn't normalized
+    pub fn transform_normal(&self, n: Vec3<f32>) -> Vec3<f32> {
+        let m = &self.0;
+        let col = |i: usize| m[0][i] * n.x() + m[1][i] * n.y() + m[2][i] * n.z();
+        Vec3::new(col(0), col(1), col(2))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Mat4(m)
+    }
+}
+
+// unit quaternion, only used to interpolate rotations
+#[derive(Debug, Clone, Copy)]
+pub struct Quat {
+    pub w: f32,
+    pub v: Vec3<f32>,
+}
+
+impl Quat {
+    pub fn identity() -> Self {
+        Self {
+            w: 1.0,
+            v: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn from_axis_angle(axis: Vec3<f32>, degrees: f32) -> Self {
+        let a = axis / axis.length_squared().sqrt();
+        let (sin, cos) = (degrees.to_radians() * 0.5).sin_cos();
+        Self { w: cos, v: a * sin }
+    }
+
+    fn dot(&self, other: Quat) -> f32 {
+        self.w * other.w + self.v.dot(other.v)
+    }
+
+    // shortest arc, falls back to a normalized lerp when the two are nearly equal
+    pub fn slerp(self, other: Quat, t: f32) -> Self {
+        let mut cos = self.dot(other);
+        let other = if cos < 0.0 {
+            cos = -cos;
+            Quat {
+                w: -other.w,
+                v: -other.v,
+            }
+        } else {
+            other
+        };
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        let q = Quat {
+            w: self.w * a + other.w * b,
+            v: self.v * a + other.v * b,
+        };
+        let len = q.dot(q).sqrt();
+        Quat {
+            w: q.w / len,
+            v: q.v / len,
+        }
+    }
+
+    pub fn conjugate(self) -> Self {
+        Quat {
+            w: self.w,
+            v: -self.v,
+        }
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        let (w, x, y, z) = (self.w, self.v.x(), self.v.y(), self.v.z());
+        Mat4([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+// translation, rotation and scale kept apart so they can be interpolated
+#[derive(Debug, Clone, Copy)]
+pub struct Trs {
+    pub translation: Vec3<f32>,
+    pub rotation: Quat,
+    pub scale: Vec3<f32>,
+}
+
+impl Default for Trs {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Trs {
+    pub fn new(translation: Vec3<f32>, rotation: Quat, scale: Vec3<f32>) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn lerp(&self, other: &Trs, t: f32) -> Self {
+        Self {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+
+    // scale first, then rotate, then translate
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translate(self.translation) * self.rotation.to_mat4() * Mat4::scale(self.scale)
+    }
+
+    // built from the parts, cheaper than a general inverse
+    pub fn inverse_matrix(&self) -> Mat4 {
+        let s = self.scale;
+        Mat4::scale(Vec3::new(1.0 / s.x(), 1.0 / s.y(), 1.0 / s.z()))
+            * self.rotation.conjugate().to_mat4()
+            * Mat4::translate(-self.translation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(m: Mat4) {
+        for (i, row) in m.0.iter().enumerate() {
+            for (j, &v) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((v - expected).abs() < 1e-5, "{m:?}");
+            }
+        }
+    }
+
+    fn skewed() -> Mat4 {
+        Mat4::translate(Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotate(Vec3::new(1.0, 1.0, 0.0), 37.0)
+            * Mat4::scale(Vec3::new(2.0, 0.5, 3.0))
+            * Mat4::rotate_z(-20.0)
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let m = skewed();
+        let inv = m.inverse().unwrap();
+        assert_identity(m * inv);
+        assert_identity(inv * m);
+        let p = Vec3::new(0.3, -4.0, 2.5);
+        let back = inv.transform_point(m.transform_point(p));
+        assert!((back - p).length() < 1e-5);
+
+        // a zero on the diagonal needs the pivoting
+        let swap = Mat4([
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 2.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ]);
+        assert_identity(swap * swap.inverse().unwrap());
+        assert!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_to_the_surface() {
+        let m = skewed();
+        let inv = m.inverse().unwrap();
+        // a plane through the origin spanned by two tangents
+        let (t1, t2) = (Vec3::new(1.0, 2.0, 0.5), Vec3::new(-0.5, 0.0, 1.0));
+        let n = t1.cross(t2);
+        let n = inv.transform_normal(n);
+        for t in [t1, t2] {
+            let t = m.transform_vector(t);
+            assert!(n.dot(t).abs() < 1e-4 * n.length() * t.length());
+        }
+    }
+}
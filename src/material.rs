@@ -1,16 +1,107 @@
+use std::{f32::consts::PI, ops::BitOr};
+
 use crate::{
-    vec3::{random_unit_vec, reflect, Vec3},
+    microfacet::{
+        fr_conductor, fr_dielectric, reflect_local, refract_local, refraction_half_vector,
+        TrowbridgeReitz,
+    },
+    onb::Onb,
+    random_double,
+    vec3::{random_cosine_direction, random_unit_vec, reflect, Vec3},
     HitRecord, Ray,
 };
 
+// which kind of lobe produced a sample
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LobeFlags(u8);
+
+impl LobeFlags {
+    pub const REFLECTION: Self = Self(1);
+    pub const TRANSMISSION: Self = Self(1 << 1);
+    pub const DIFFUSE: Self = Self(1 << 2);
+    pub const GLOSSY: Self = Self(1 << 3);
+    // delta distribution, eval and pdf are always zero for these
+    pub const SPECULAR: Self = Self(1 << 4);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.contains(Self::SPECULAR)
+    }
+}
+
+impl BitOr for LobeFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+pub struct BsdfSample {
+    // unit direction towards the next vertex, world space
+    pub wi: Vec3<f32>,
+    // bsdf value f(wo, wi), for specular lobes this is the weight divided by |cos|
+    pub value: Vec3<f32>,
+    // solid angle density, 1 for specular lobes
+    pub pdf: f32,
+    pub flags: LobeFlags,
+}
+
+impl BsdfSample {
+    // f * |cos| / pdf, what the path throughput gets multiplied by
+    pub fn weight(&self, cos_factor: f32) -> Vec3<f32> {
+        self.value * (cos_factor / self.pdf)
+    }
+
+    fn specular(wi: Vec3<f32>, weight: Vec3<f32>, normal: Vec3<f32>, flags: LobeFlags) -> Self {
+        Self {
+            wi,
+            value: weight / wi.dot(normal).abs(),
+            pdf: 1.0,
+            flags: flags | LobeFlags::SPECULAR,
+        }
+    }
+}
+
+// wo points back along the incoming ray and wi towards the light, both unit length in world space
 pub trait Material {
+    fn sample(&self, r_in: &Ray<f32>, rec: &HitRecord) -> Option<BsdfSample>;
+
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3<f32>, _wi: Vec3<f32>) -> Vec3<f32> {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3<f32>, _wi: Vec3<f32>) -> f32 {
+        0.0
+    }
+
+    // projected solid angle term for light arriving along wi, phase functions in a
+    // volume have no surface to project onto
+    fn cos_factor(&self, rec: &HitRecord, wi: Vec3<f32>) -> f32 {
+        wi.dot(rec.normal).abs()
+    }
+
+    // radiance leaving the surface towards the incoming ray
+    fn emitted(&self, _rec: &HitRecord) -> Vec3<f32> {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
     fn scatter(
         &self,
         r_in: &Ray<f32>,
         rec: &HitRecord,
         attenuation: &mut Vec3<f32>,
         scattered: &mut Ray<f32>,
-    ) -> bool;
+    ) -> bool {
+        let Some(sample) = self.sample(r_in, rec) else {
+            return false;
+        };
+        *attenuation = sample.weight(self.cos_factor(rec, sample.wi));
+        *scattered = Ray::with_time(rec.p, sample.wi, r_in.time());
+        true
+    }
 }
 
 #[derive(Default)]
@@ -18,30 +109,51 @@ pub struct Lambertian {
     pub albedo: Vec3<f32>,
 }
 
-macro_rules! near_zero_vec {
-    ($v:expr) => {{
-        let s = 1e-8;
-        $v.x().abs() < s && $v.y().abs() < s && $v.z().abs() < s
-    }};
-}
-
 impl Lambertian {}
 
 impl Material for Lambertian {
-    fn scatter(
-        &self,
-        _r_in: &Ray<f32>,
-        rec: &HitRecord,
-        attenuation: &mut Vec3<f32>,
-        scattered: &mut Ray<f32>,
-    ) -> bool {
-        let mut direction = rec.normal + random_unit_vec();
-        if near_zero_vec!(direction) {
-            direction = rec.normal;
+    fn sample(&self, _r_in: &Ray<f32>, rec: &HitRecord) -> Option<BsdfSample> {
+        let frame = Onb::new(rec.normal);
+        let wi = random_cosine_direction();
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi: frame.to_world(wi),
+            value: self.albedo / PI,
+            pdf: wi.z() / PI,
+            flags: LobeFlags::REFLECTION | LobeFlags::DIFFUSE,
+        })
+    }
+
+    fn eval(&self, rec: &HitRecord, _wo: Vec3<f32>, wi: Vec3<f32>) -> Vec3<f32> {
+        if wi.dot(rec.normal) <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        self.albedo / PI
+    }
+
+    fn pdf(&self, rec: &HitRecord, _wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        wi.dot(rec.normal).max(0.0) / PI
+    }
+}
+
+// emits on its front face and absorbs everything
+pub struct DiffuseLight {
+    pub emit: Vec3<f32>,
+}
+
+impl Material for DiffuseLight {
+    fn sample(&self, _r_in: &Ray<f32>, _rec: &HitRecord) -> Option<BsdfSample> {
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Vec3<f32> {
+        if rec.front_face {
+            self.emit
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
         }
-        *scattered = Ray::new(rec.p, direction);
-        *attenuation = self.albedo;
-        true
     }
 }
 
@@ -69,18 +181,346 @@ pub struct Metal {
 impl Metal {}
 
 impl Material for Metal {
-    fn scatter(
-        &self,
-        r_in: &Ray<f32>,
-        rec: &HitRecord,
-        attenuation: &mut Vec3<f32>,
-        scattered: &mut Ray<f32>,
-    ) -> bool {
+    // the fuzzed mirror has no density we can evaluate, so it is treated as specular
+    fn sample(&self, r_in: &Ray<f32>, rec: &HitRecord) -> Option<BsdfSample> {
         let reflected =
             unit_v!(reflect(r_in.direction(), rec.normal)) + random_unit_vec() * self.fuzz;
-        let scattered_r = Ray::new(rec.p, reflected);
-        *scattered = scattered_r;
-        *attenuation = self.attenuation;
-        scattered_r.direction().dot(rec.normal) > 0.0
+        if reflected.dot(rec.normal) <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample::specular(
+            unit_v!(reflected),
+            self.attenuation,
+            rec.normal,
+            LobeFlags::REFLECTION,
+        ))
+    }
+}
+
+// GGX microfacet conductor with a complex index of refraction
+pub struct Conductor {
+    pub eta: Vec3<f32>,
+    pub k: Vec3<f32>,
+    pub distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3<f32>, k: Vec3<f32>, roughness: f32) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::from_roughness(roughness),
+        }
+    }
+
+    // rgb values taken at roughly 650nm, 550nm and 450nm
+    // https://refractiveindex.info
+    pub fn gold(roughness: f32) -> Self {
+        Self::new(
+            Vec3::new(0.143, 0.374, 1.442),
+            Vec3::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f32) -> Self {
+        Self::new(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f32) -> Self {
+        Self::new(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+}
+
+impl Material for Conductor {
+    fn sample(&self, r_in: &Ray<f32>, rec: &HitRecord) -> Option<BsdfSample> {
+        let frame = Onb::new(rec.normal);
+        let wo = frame.to_local(-unit_v!(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.effectively_smooth() {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            return Some(BsdfSample::specular(
+                frame.to_world(wi),
+                fr_conductor(wo.z(), self.eta, self.k),
+                rec.normal,
+                LobeFlags::REFLECTION,
+            ));
+        }
+
+        let wm = self.distribution.sample_wm(wo);
+        let wi = reflect_local(wo, wm);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        let d = self.distribution;
+        Some(BsdfSample {
+            wi: frame.to_world(wi),
+            value: fr_conductor(wo.dot(wm), self.eta, self.k) * d.d(wm) * d.g(wo, wi)
+                / (4.0 * wo.z() * wi.z()),
+            pdf: d.d_visible(wo, wm) / (4.0 * wo.dot(wm)),
+            flags: LobeFlags::REFLECTION | LobeFlags::GLOSSY,
+        })
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> Vec3<f32> {
+        let frame = Onb::new(rec.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if self.distribution.effectively_smooth() || wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let wm = unit_v!(wo + wi);
+        let d = self.distribution;
+        fr_conductor(wo.dot(wm), self.eta, self.k) * d.d(wm) * d.g(wo, wi) / (4.0 * wo.z() * wi.z())
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        let frame = Onb::new(rec.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if self.distribution.effectively_smooth() || wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let wm = unit_v!(wo + wi);
+        self.distribution.d_visible(wo, wm) / (4.0 * wo.dot(wm))
+    }
+}
+
+// GGX microfacet glass, reflects and transmits
+pub struct RoughDielectric {
+    pub ior: f32,
+    pub distribution: TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+    pub fn new(ior: f32, roughness: f32) -> Self {
+        Self {
+            ior,
+            distribution: TrowbridgeReitz::from_roughness(roughness),
+        }
+    }
+
+    fn eta(&self, rec: &HitRecord) -> f32 {
+        if rec.front_face {
+            self.ior
+        } else {
+            1.0 / self.ior
+        }
+    }
+
+    // returns f and pdf together since they share most of the work
+    fn eval_pdf(&self, rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> (Vec3<f32>, f32) {
+        let zero = (Vec3::new(0.0, 0.0, 0.0), 0.0);
+        let frame = Onb::new(rec.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if self.distribution.effectively_smooth() || wo.z() <= 0.0 || wi.z() == 0.0 {
+            return zero;
+        }
+        let eta = self.eta(rec);
+        let d = self.distribution;
+
+        if wi.z() > 0.0 {
+            let wm = unit_v!(wo + wi);
+            let f = fr_dielectric(wo.dot(wm), eta);
+            let value = f * d.d(wm) * d.g(wo, wi) / (4.0 * wo.z() * wi.z());
+            let pdf = d.d_visible(wo, wm) / (4.0 * wo.dot(wm)) * f;
+            (Vec3::new(value, value, value), pdf)
+        } else {
+            let Some(wm) = refraction_half_vector(wo, wi, eta) else {
+                return zero;
+            };
+            let t = 1.0 - fr_dielectric(wo.dot(wm), eta);
+            let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2);
+            // radiance is compressed when entering the denser medium
+            let value = t
+                * d.d(wm)
+                * d.g(wo, wi)
+                * (wi.dot(wm) * wo.dot(wm) / (wi.z() * wo.z() * denom)).abs()
+                / (eta * eta);
+            let pdf = d.d_visible(wo, wm) * wi.dot(wm).abs() / denom * t;
+            (Vec3::new(value, value, value), pdf)
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn sample(&self, r_in: &Ray<f32>, rec: &HitRecord) -> Option<BsdfSample> {
+        let frame = Onb::new(rec.normal);
+        let wo_world = -unit_v!(r_in.direction());
+        let wo = frame.to_local(wo_world);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let eta = self.eta(rec);
+        let smooth = self.distribution.effectively_smooth();
+        let wm = if smooth {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            self.distribution.sample_wm(wo)
+        };
+
+        let reflect = random_double() < fr_dielectric(wo.dot(wm), eta);
+        let (wi, flags) = if reflect {
+            (reflect_local(wo, wm), LobeFlags::REFLECTION)
+        } else {
+            (refract_local(wo, wm, eta)?, LobeFlags::TRANSMISSION)
+        };
+        if (wi.z() > 0.0) != reflect || wi.z() == 0.0 {
+            return None;
+        }
+
+        if smooth {
+            // the fresnel term cancels with the probability of picking the lobe
+            let weight = if reflect { 1.0 } else { 1.0 / (eta * eta) };
+            return Some(BsdfSample::specular(
+                frame.to_world(wi),
+                Vec3::new(weight, weight, weight),
+                rec.normal,
+                flags,
+            ));
+        }
+
+        let wi = frame.to_world(wi);
+        let (value, pdf) = self.eval_pdf(rec, wo_world, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            value,
+            pdf,
+            flags: flags | LobeFlags::GLOSSY,
+        })
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> Vec3<f32> {
+        self.eval_pdf(rec, wo, wi).0
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        self.eval_pdf(rec, wo, wi).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // midpoint rule over the sphere in theta and phi, fine in theta for the narrow lobes
+    // around the poles
+    fn sphere(f: impl Fn(Vec3<f32>) -> f64) -> f64 {
+        let (ntheta, nphi) = (3000, 300);
+        let (dtheta, dphi) = (PI / ntheta as f32, 2.0 * PI / nphi as f32);
+        let mut sum = 0.0;
+        for i in 0..ntheta {
+            let theta = (i as f32 + 0.5) * dtheta;
+            let mut ring = 0.0;
+            for j in 0..nphi {
+                let phi = (j as f32 + 0.5) * dphi;
+                ring += f(Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ));
+            }
+            sum += ring * (theta.sin() * dtheta * dphi) as f64;
+        }
+        sum
+    }
+
+    fn hit(front_face: bool) -> HitRecord {
+        HitRecord {
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            front_face,
+            ..HitRecord::default()
+        }
+    }
+
+    // samples against eval and pdf and the share of samples taken against the integral of
+    // the pdf, returns the albedo
+    fn check(material: &dyn Material, rec: &HitRecord, wo: Vec3<f32>) -> Vec3<f32> {
+        let n = 100_000;
+        let ray = Ray::new(wo, -wo);
+        let (mut taken, mut albedo) = (0, Vec3::new(0.0, 0.0, 0.0));
+        let (mut error, mut outliers) = (0.0, 0);
+        for _ in 0..n {
+            let Some(s) = material.sample(&ray, rec) else {
+                continue;
+            };
+            taken += 1;
+            let pdf = material.pdf(rec, wo, s.wi);
+            let f = material.eval(rec, wo, s.wi);
+            let e = ((s.pdf - pdf).abs() / pdf.max(1.0))
+                .max((s.value - f).length() / f.length().max(1.0));
+            // unit_v! is only good to ~1e-3 and the refraction half vector is ill conditioned
+            // near the critical angle, so a few samples may be off but the average may not
+            if e > 0.1 {
+                outliers += 1;
+            }
+            error += e as f64;
+            albedo += s.weight(material.cos_factor(rec, s.wi)) / n as f32;
+        }
+        assert!(outliers * 1000 < taken, "{outliers} of {taken}");
+        assert!(error / (taken as f64) < 1e-2, "{}", error / taken as f64);
+        let total = sphere(|wi| material.pdf(rec, wo, wi) as f64);
+        let share = taken as f64 / n as f64;
+        assert!((total - share).abs() < 1e-2, "{total} {share}");
+        albedo
+    }
+
+    fn direction(theta: f32) -> Vec3<f32> {
+        Vec3::new(theta.sin(), 0.0, theta.cos())
+    }
+
+    #[test]
+    fn conductor_samples_match_its_density() {
+        for roughness in [0.3, 0.6, 0.9] {
+            for theta in [0.0, 0.7, 1.3] {
+                let albedo = check(&Conductor::gold(roughness), &hit(true), direction(theta));
+                // single scattering loses energy, never gains it, and gold stays red
+                assert!(
+                    albedo.x() <= 1.0 + 1e-2,
+                    "{roughness} {theta}: {}",
+                    albedo.x()
+                );
+                assert!(albedo.z() < albedo.x());
+            }
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_samples_match_its_density() {
+        for roughness in [0.3, 0.6] {
+            for theta in [0.0, 0.7, 1.3] {
+                for front_face in [true, false] {
+                    let material = RoughDielectric::new(1.5, roughness);
+                    let albedo = check(&material, &hit(front_face), direction(theta)).x();
+                    // radiance grows by 1 / eta^2 leaving the glass, never beyond that
+                    let eta = material.eta(&hit(front_face));
+                    assert!(
+                        albedo <= 1.0f32.max(1.0 / (eta * eta)) + 1e-2,
+                        "{roughness} {theta} {front_face}: {albedo}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lambertian_furnace() {
+        let white = Lambertian {
+            albedo: Vec3::new(1.0, 1.0, 1.0),
+        };
+        let albedo = check(&white, &hit(true), direction(0.5));
+        assert!((albedo.x() - 1.0).abs() < 1e-3);
     }
 }
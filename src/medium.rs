@@ -0,0 +1,218 @@
+use std::{f32::consts::PI, rc::Rc};
+
+use crate::{
+    aabb::Aabb,
+    interval::{Interval, INTERVAL_UNIVERSE},
+    material::{BsdfSample, LobeFlags, Material},
+    onb::Onb,
+    random_double,
+    ray::Ray,
+    vec3::Vec3,
+    HitRecord, Hittable,
+};
+
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+macro_rules! unit_v {
+    ($v:expr) => {
+        $v / f32_len!($v.length_squared())
+    };
+}
+
+// homogeneous volume filling a closed boundary. the free flight distance is sampled
+// every time a ray passes through, so a hit is a scattering event inside the volume
+pub struct ConstantMedium {
+    pub boundary: Box<dyn Hittable>,
+    pub neg_inv_density: f32,
+    pub phase_function: Rc<Box<dyn Material>>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Box<dyn Hittable>,
+        density: f32,
+        phase_function: Box<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Rc::new(phase_function),
+        }
+    }
+}
+
+impl ConstantMedium {
+    // where the ray is inside the boundary, clipped to ray_t, even if it starts inside
+    fn span(&self, r: &Ray<f32>, ray_t: Interval) -> Option<(f32, f32)> {
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+        if !self
+            .boundary
+            .hit(r, INTERVAL_UNIVERSE, &mut rec1, self.boundary.material())
+        {
+            return None;
+        }
+        if !self.boundary.hit(
+            r,
+            Interval::new(rec1.t + 0.0001, f32::INFINITY),
+            &mut rec2,
+            self.boundary.material(),
+        ) {
+            return None;
+        }
+
+        let t_enter = rec1.t.max(ray_t.min).max(0.0);
+        let t_exit = rec2.t.min(ray_t.max);
+        if t_enter >= t_exit {
+            return None;
+        }
+        Some((t_enter, t_exit))
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let Some((t_enter, t_exit)) = self.span(r, ray_t) else {
+            return false;
+        };
+
+        let ray_length = f32_len!(r.direction().length_squared());
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double().ln();
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        *rec = HitRecord {
+            p: r.at(t),
+            // arbitrary, phase functions don't use it
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            t,
+            u: 0.0,
+            v: 0.0,
+            front_face: true,
+            material: Some(material),
+            color: None,
+        };
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.phase_function.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    // beer-lambert, no need to sample
+    fn transmittance(&self, r: &Ray<f32>, ray_t: Interval) -> f32 {
+        match self.span(r, ray_t) {
+            Some((t_enter, t_exit)) => {
+                let distance = (t_exit - t_enter) * f32_len!(r.direction().length_squared());
+                (self.neg_inv_density.recip() * distance).exp()
+            }
+            None => 1.0,
+        }
+    }
+}
+
+// scatters uniformly in every direction
+pub struct Isotropic {
+    pub albedo: Vec3<f32>,
+}
+
+impl Material for Isotropic {
+    fn sample(&self, _r_in: &Ray<f32>, _rec: &HitRecord) -> Option<BsdfSample> {
+        let z = 1.0 - 2.0 * random_double();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        Some(BsdfSample {
+            wi: Vec3::new(r * phi.cos(), r * phi.sin(), z),
+            value: self.albedo / (4.0 * PI),
+            pdf: 1.0 / (4.0 * PI),
+            flags: LobeFlags::REFLECTION | LobeFlags::TRANSMISSION | LobeFlags::DIFFUSE,
+        })
+    }
+
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3<f32>, _wi: Vec3<f32>) -> Vec3<f32> {
+        self.albedo / (4.0 * PI)
+    }
+
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3<f32>, _wi: Vec3<f32>) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn cos_factor(&self, _rec: &HitRecord, _wi: Vec3<f32>) -> f32 {
+        1.0
+    }
+}
+
+// henyey-greenstein phase function, g > 0 scatters forward and g < 0 backward
+pub struct HenyeyGreenstein {
+    pub albedo: Vec3<f32>,
+    pub g: f32,
+}
+
+impl HenyeyGreenstein {
+    // cos_theta is between wo and wi, both pointing away from the scattering point
+    fn phase(&self, cos_theta: f32) -> f32 {
+        let denom = 1.0 + self.g * self.g + 2.0 * self.g * cos_theta;
+        (1.0 - self.g * self.g) / (4.0 * PI * denom * denom.max(1e-8).sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn sample(&self, r_in: &Ray<f32>, _rec: &HitRecord) -> Option<BsdfSample> {
+        let wo = -unit_v!(r_in.direction());
+        let g = self.g;
+        let u = random_double();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let sqr_term = (1.0 - g * g) / (1.0 + g - 2.0 * g * u);
+            -(1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        }
+        .clamp(-1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        let wi = Onb::new(wo).to_world(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        let p = self.phase(cos_theta);
+        Some(BsdfSample {
+            wi,
+            value: self.albedo * p,
+            pdf: p,
+            flags: LobeFlags::REFLECTION | LobeFlags::TRANSMISSION | LobeFlags::GLOSSY,
+        })
+    }
+
+    fn eval(&self, _rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> Vec3<f32> {
+        self.albedo * self.phase(wo.dot(wi))
+    }
+
+    fn pdf(&self, _rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        self.phase(wo.dot(wi))
+    }
+
+    fn cos_factor(&self, _rec: &HitRecord, _wi: Vec3<f32>) -> f32 {
+        1.0
+    }
+}
@@ -0,0 +1,132 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb, bvh::Bvh, interval::Interval, material::Material, primitives::intersect_triangle,
+    ray::Ray, vec3::Vec3, HitRecord, Hittable,
+};
+
+// indexed triangles as the file loaders produce them. normals, colors and uvs are either
+// empty or one per position
+#[derive(Default)]
+pub struct MeshData {
+    pub positions: Vec<Vec3<f32>>,
+    pub normals: Vec<Vec3<f32>>,
+    pub colors: Vec<Vec3<f32>>,
+    pub uvs: Vec<(f32, f32)>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl MeshData {
+    pub fn bounding_box(&self) -> Aabb {
+        self.positions.iter().fold(Aabb::EMPTY, |acc, &p| {
+            Aabb::enclosing(&acc, &Aabb::from_points(p, p))
+        })
+    }
+
+    // drops triangles pointing past the vertex list instead of panicking on them later
+    fn validate(&mut self) {
+        let n = self.positions.len();
+        self.triangles.retain(|t| t.iter().all(|&i| i < n));
+        if self.normals.len() != n {
+            self.normals.clear();
+        }
+        if self.colors.len() != n {
+            self.colors.clear();
+        }
+        if self.uvs.len() != n {
+            self.uvs.clear();
+        }
+    }
+}
+
+// triangle mesh with its own bvh. per vertex normals are interpolated when present and
+// vertex colors end up in HitRecord::color for VertexColor textures
+pub struct TriangleMesh {
+    data: MeshData,
+    bvh: Bvh,
+    pub material: Rc<Box<dyn Material>>,
+}
+
+impl TriangleMesh {
+    pub fn new(mut data: MeshData, material: Rc<Box<dyn Material>>) -> Self {
+        data.validate();
+        let boxes: Vec<Aabb> = data
+            .triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| data.positions[i]);
+                Aabb::enclosing(&Aabb::from_points(a, b), &Aabb::from_points(c, c)).padded()
+            })
+            .collect();
+        Self {
+            bvh: Bvh::new(&boxes),
+            data,
+            material,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.data.triangles.len()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let positions = &self.data.positions;
+        let mut best = None;
+        self.bvh.hit(r, ray_t, |i, ray_t| {
+            let [a, b, c] = self.data.triangles[i].map(|v| positions[v]);
+            let (t, b1, b2) = intersect_triangle(r, a, b, c, &ray_t)?;
+            best = Some((i, t, b1, b2));
+            Some(t)
+        });
+        let Some((i, t, b1, b2)) = best else {
+            return false;
+        };
+
+        let tri = self.data.triangles[i];
+        let b0 = 1.0 - b1 - b2;
+        let lerp = |v: &[Vec3<f32>]| v[tri[0]] * b0 + v[tri[1]] * b1 + v[tri[2]] * b2;
+        let outward_normal = if self.data.normals.is_empty() {
+            let [a, b, c] = tri.map(|v| positions[v]);
+            (b - a).cross(c - a)
+        } else {
+            lerp(&self.data.normals)
+        };
+        let (u, v) = if self.data.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let uv = tri.map(|v| self.data.uvs[v]);
+            (
+                uv[0].0 * b0 + uv[1].0 * b1 + uv[2].0 * b2,
+                uv[0].1 * b0 + uv[1].1 * b1 + uv[2].1 * b2,
+            )
+        };
+        *rec = HitRecord {
+            p: r.at(t),
+            normal: outward_normal / outward_normal.length_squared().sqrt(),
+            t,
+            u,
+            v,
+            front_face: false,
+            material: Some(material),
+            color: (!self.data.colors.is_empty()).then(|| lerp(&self.data.colors)),
+        };
+        rec.set_face_normal(r, rec.normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
@@ -0,0 +1,343 @@
+use std::f32::consts::PI;
+
+use crate::{random_double, vec3::Vec3};
+
+// all directions are in the local shading frame, the normal is +z
+
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+macro_rules! unit_v {
+    ($v:expr) => {
+        $v / f32_len!($v.length_squared())
+    };
+}
+
+#[inline(always)]
+pub fn cos2_theta(w: Vec3<f32>) -> f32 {
+    w.z() * w.z()
+}
+
+#[inline(always)]
+pub fn sin2_theta(w: Vec3<f32>) -> f32 {
+    (1.0 - cos2_theta(w)).max(0.0)
+}
+
+#[inline(always)]
+pub fn tan2_theta(w: Vec3<f32>) -> f32 {
+    sin2_theta(w) / cos2_theta(w)
+}
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals
+// https://jcgt.org/published/0007/04/01/paper.pdf
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> Self {
+        Self {
+            alpha_x: alpha_x.max(1e-4),
+            alpha_y: alpha_y.max(1e-4),
+        }
+    }
+
+    // perceptual roughness in [0, 1] to alpha, same mapping as disney / unreal
+    pub fn from_roughness(roughness: f32) -> Self {
+        let alpha = roughness * roughness;
+        Self::new(alpha, alpha)
+    }
+
+    // below this the lobe is narrower than anything we can resolve
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    pub fn d(&self, wm: Vec3<f32>) -> f32 {
+        let tan2 = tan2_theta(wm);
+        if tan2.is_infinite() || tan2.is_nan() {
+            return 0.0;
+        }
+        let cos4 = cos2_theta(wm) * cos2_theta(wm);
+        let sin2 = sin2_theta(wm);
+        let (cos2_phi, sin2_phi) = if sin2 == 0.0 {
+            (1.0, 0.0)
+        } else {
+            (wm.x() * wm.x() / sin2, wm.y() * wm.y() / sin2)
+        };
+        let e = tan2
+            * (cos2_phi / (self.alpha_x * self.alpha_x) + sin2_phi / (self.alpha_y * self.alpha_y));
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos4 * (1.0 + e) * (1.0 + e))
+    }
+
+    pub fn lambda(&self, w: Vec3<f32>) -> f32 {
+        let tan2 = tan2_theta(w);
+        if tan2.is_infinite() || tan2.is_nan() {
+            return 0.0;
+        }
+        let sin2 = sin2_theta(w);
+        let alpha2 = if sin2 == 0.0 {
+            self.alpha_x * self.alpha_x
+        } else {
+            (w.x() * w.x() * self.alpha_x * self.alpha_x
+                + w.y() * w.y() * self.alpha_y * self.alpha_y)
+                / sin2
+        };
+        ((1.0 + alpha2 * tan2).sqrt() - 1.0) * 0.5
+    }
+
+    // smith masking
+    pub fn g1(&self, w: Vec3<f32>) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height correlated smith masking-shadowing
+    pub fn g(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // distribution of normals visible from w
+    pub fn d_visible(&self, w: Vec3<f32>, wm: Vec3<f32>) -> f32 {
+        self.g1(w) / w.z().abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // sample a visible normal as seen from w
+    pub fn sample_wm(&self, w: Vec3<f32>) -> Vec3<f32> {
+        // transform w to the hemispherical configuration
+        let mut wh = unit_v!(Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()));
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+        let lensq = wh.x() * wh.x() + wh.y() * wh.y();
+        let t1 = if lensq > 0.0 {
+            Vec3::new(-wh.y(), wh.x(), 0.0) / lensq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(t1);
+
+        // uniformly sample a disk, then warp it to the projected hemisphere
+        let r = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p1 = r * phi.cos();
+        let mut p2 = r * phi.sin();
+        let s = 0.5 * (1.0 + wh.z());
+        p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * p2;
+        let nh = t1 * p1 + t2 * p2 + wh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        unit_v!(Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6)
+        ))
+    }
+}
+
+// unpolarized fresnel reflectance at a dielectric interface, eta is eta_t / eta_i
+pub fn fr_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i, eta)
+    };
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).max(0.0).sqrt();
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parl * r_parl + r_perp * r_perp) * 0.5
+}
+
+// unpolarized fresnel reflectance at a conductor with complex ior eta + ik
+fn fr_complex(cos_theta_i: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    (rp + rs) * 0.5
+}
+
+pub fn fr_conductor(cos_theta_i: f32, eta: Vec3<f32>, k: Vec3<f32>) -> Vec3<f32> {
+    Vec3::new(
+        fr_complex(cos_theta_i, eta.x(), k.x()),
+        fr_complex(cos_theta_i, eta.y(), k.y()),
+        fr_complex(cos_theta_i, eta.z(), k.z()),
+    )
+}
+
+#[inline(always)]
+pub fn reflect_local(wo: Vec3<f32>, n: Vec3<f32>) -> Vec3<f32> {
+    -wo + n * wo.dot(n) * 2.0
+}
+
+// refract wo through a surface with normal n, eta is eta_t / eta_i on the side n points to
+pub fn refract_local(wo: Vec3<f32>, n: Vec3<f32>, eta: f32) -> Option<Vec3<f32>> {
+    let cos_theta_i = wo.dot(n);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(-wo / eta + n * (cos_theta_i / eta - cos_theta_t))
+}
+
+// generalized half vector between wo and a refracted wi, flipped to face the normal,
+// None when either direction sees the back of the microfacet
+pub fn refraction_half_vector(wo: Vec3<f32>, wi: Vec3<f32>, eta: f32) -> Option<Vec3<f32>> {
+    let mut wm = unit_v!(wi * eta + wo);
+    if wm.z() < 0.0 {
+        wm = -wm;
+    }
+    if wo.dot(wm) <= 0.0 || wi.dot(wm) >= 0.0 {
+        return None;
+    }
+    Some(wm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // midpoint rule over the upper hemisphere, uniform in cos theta and phi
+    fn hemisphere(f: impl Fn(Vec3<f32>) -> f64) -> f64 {
+        let (nu, nphi) = (1000, 400);
+        let mut sum = 0.0;
+        for i in 0..nu {
+            let z = (i as f32 + 0.5) / nu as f32;
+            let r = (1.0 - z * z).sqrt();
+            for j in 0..nphi {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / nphi as f32;
+                sum += f(Vec3::new(r * phi.cos(), r * phi.sin(), z));
+            }
+        }
+        sum * 2.0 * std::f64::consts::PI / (nu * nphi) as f64
+    }
+
+    fn direction(theta: f32, phi: f32) -> Vec3<f32> {
+        Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        )
+    }
+
+    const DISTRIBUTIONS: [(f32, f32); 3] = [(0.3, 0.3), (0.2, 0.5), (0.7, 0.7)];
+
+    #[test]
+    fn projected_normals_integrate_to_one() {
+        for (ax, ay) in DISTRIBUTIONS {
+            let d = TrowbridgeReitz::new(ax, ay);
+            let total = hemisphere(|wm| (d.d(wm) * wm.z()) as f64);
+            assert!((total - 1.0).abs() < 1e-2, "{ax} {ay}: {total}");
+        }
+    }
+
+    #[test]
+    fn visible_normals_integrate_to_one() {
+        for (ax, ay) in DISTRIBUTIONS {
+            let d = TrowbridgeReitz::new(ax, ay);
+            for wo in [
+                direction(0.0, 0.0),
+                direction(0.6, 0.4),
+                direction(1.2, 2.0),
+            ] {
+                let visible = |wm: Vec3<f32>| {
+                    if wo.dot(wm) > 0.0 {
+                        d.d_visible(wo, wm)
+                    } else {
+                        0.0
+                    }
+                };
+                let total = hemisphere(|wm| visible(wm) as f64);
+                assert!((total - 1.0).abs() < 1e-2, "{ax} {ay}: {total}");
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_normals_follow_the_visible_distribution() {
+        let n = 200_000;
+        for (ax, ay) in DISTRIBUTIONS {
+            let d = TrowbridgeReitz::new(ax, ay);
+            let wo = direction(0.9, 0.7);
+            // mean of the sampled normals against the same moment of the density
+            let mut mean = [0.0f64; 3];
+            for _ in 0..n {
+                let wm = d.sample_wm(wo);
+                assert!(wo.dot(wm) > 0.0);
+                for (m, v) in mean.iter_mut().zip([wm.x(), wm.y(), wm.z()]) {
+                    *m += v as f64 / n as f64;
+                }
+            }
+            let expected = [0, 1, 2].map(|k| {
+                hemisphere(|wm| {
+                    let v = [wm.x(), wm.y(), wm.z()][k];
+                    if wo.dot(wm) > 0.0 {
+                        (v * d.d_visible(wo, wm)) as f64
+                    } else {
+                        0.0
+                    }
+                })
+            });
+            for k in 0..3 {
+                assert!(
+                    (mean[k] - expected[k]).abs() < 1e-2,
+                    "{ax} {ay} {mean:?} {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn conductor_fresnel() {
+        // without absorption a conductor is a dielectric
+        for cos in [1.0, 0.7, 0.3, 0.05] {
+            let (a, b) = (fr_complex(cos, 1.5, 0.0), fr_dielectric(cos, 1.5));
+            assert!((a - b).abs() < 1e-5, "{cos}: {a} {b}");
+        }
+        // normal incidence has a closed form, grazing reflects everything
+        let (eta, k) = (0.2, 3.9);
+        let normal = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert!((fr_complex(1.0, eta, k) - normal).abs() < 1e-5);
+        assert!((fr_complex(0.0, eta, k) - 1.0).abs() < 1e-5);
+        for i in 0..=20 {
+            let f = fr_complex(i as f32 / 20.0, eta, k);
+            assert!((0.0..=1.0).contains(&f));
+        }
+    }
+
+    #[test]
+    fn refraction_inverts_through_the_half_vector() {
+        let wm = direction(0.2, 1.0);
+        let wo = direction(0.5, 3.0);
+        let eta = 1.5;
+        let wi = refract_local(wo, wm, eta).unwrap();
+        assert!((wi.length() - 1.0).abs() < 1e-4);
+        let half = refraction_half_vector(wo, wi, eta).unwrap();
+        assert!((half - wm).length() < 1e-3);
+        // from inside past the critical angle there is nothing to refract into
+        assert!(refract_local(direction(1.2, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0 / eta).is_none());
+    }
+}
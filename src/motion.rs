@@ -0,0 +1,142 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb, hit_sphere, interval::Interval, mat4::Trs, material::Material, ray::Ray,
+    vec3::Vec3, HitRecord, Hittable,
+};
+
+// fraction of the way from time0 to time1, held at the ends
+fn motion_fraction(time: f32, time0: f32, time1: f32) -> f32 {
+    if time1 <= time0 {
+        return 0.0;
+    }
+    ((time - time0) / (time1 - time0)).clamp(0.0, 1.0)
+}
+
+// sphere moving in a straight line from center0 at time0 to center1 at time1
+pub struct MovingSphere {
+    pub center0: Vec3<f32>,
+    pub center1: Vec3<f32>,
+    pub time0: f32,
+    pub time1: f32,
+    pub radius: f32,
+    pub material: Rc<Box<dyn Material>>,
+}
+
+impl MovingSphere {
+    pub fn center(&self, time: f32) -> Vec3<f32> {
+        let t = motion_fraction(time, self.time0, self.time1);
+        self.center0 + (self.center1 - self.center0) * t
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        hit_sphere(self.center(r.time()), self.radius, r, ray_t, rec, material)
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::enclosing(
+            &Aabb::from_points(self.center0 - r, self.center0 + r),
+            &Aabb::from_points(self.center1 - r, self.center1 + r),
+        )
+    }
+}
+
+// instance whose transform is interpolated between two keys, rotations are slerped so
+// a spinning object keeps its shape
+pub struct AnimatedInstance {
+    pub object: Rc<dyn Hittable>,
+    start: Trs,
+    end: Trs,
+    time0: f32,
+    time1: f32,
+    bbox: Aabb,
+}
+
+impl AnimatedInstance {
+    pub fn new(object: Rc<dyn Hittable>, start: Trs, end: Trs, time0: f32, time1: f32) -> Self {
+        // the corners don't move in straight lines while rotating, so sample the motion
+        // densely and pad a little for the arcs between samples
+        let local = object.bounding_box();
+        let steps = 32;
+        let mut bbox = Aabb::EMPTY;
+        for i in 0..=steps {
+            let trs = start.lerp(&end, i as f32 / steps as f32);
+            bbox = Aabb::enclosing(&bbox, &local.transformed(&trs.matrix()));
+        }
+        let pad = |i: Interval| i.expand(i.size() * 0.01);
+        bbox = Aabb {
+            x: pad(bbox.x),
+            y: pad(bbox.y),
+            z: pad(bbox.z),
+        };
+        Self {
+            object,
+            start,
+            end,
+            time0,
+            time1,
+            bbox,
+        }
+    }
+
+    fn at(&self, time: f32) -> Trs {
+        self.start
+            .lerp(&self.end, motion_fraction(time, self.time0, self.time1))
+    }
+}
+
+impl Hittable for AnimatedInstance {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let trs = self.at(r.time());
+        let inverse = trs.inverse_matrix();
+        let local = Ray::with_time(
+            inverse.transform_point(r.origin()),
+            inverse.transform_vector(r.direction()),
+            r.time(),
+        );
+        if !self.object.hit(&local, ray_t, rec, material) {
+            return false;
+        }
+        rec.p = trs.matrix().transform_point(rec.p);
+        let n = inverse.transform_normal(rec.normal);
+        rec.normal = n / n.length_squared().sqrt();
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.object.material()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray<f32>, ray_t: Interval) -> f32 {
+        let inverse = self.at(r.time()).inverse_matrix();
+        let local = Ray::with_time(
+            inverse.transform_point(r.origin()),
+            inverse.transform_vector(r.direction()),
+            r.time(),
+        );
+        self.object.transmittance(&local, ray_t)
+    }
+}
@@ -0,0 +1,44 @@
+use crate::vec3::Vec3;
+
+// orthonormal basis around a unit normal, w is the normal
+// https://graphics.pixar.com/library/OrthonormalB/paper.pdf
+#[derive(Clone, Copy)]
+pub struct Onb {
+    axis: [Vec3<f32>; 3],
+}
+
+impl Onb {
+    pub fn new(n: Vec3<f32>) -> Self {
+        let sign = 1.0_f32.copysign(n.z());
+        let a = -1.0 / (sign + n.z());
+        let b = n.x() * n.y() * a;
+        let u = Vec3::new(1.0 + sign * n.x() * n.x() * a, sign * b, -sign * n.x());
+        let v = Vec3::new(b, sign + n.y() * n.y() * a, -n.y());
+        Self { axis: [u, v, n] }
+    }
+
+    #[inline(always)]
+    pub fn u(&self) -> Vec3<f32> {
+        self.axis[0]
+    }
+
+    #[inline(always)]
+    pub fn v(&self) -> Vec3<f32> {
+        self.axis[1]
+    }
+
+    #[inline(always)]
+    pub fn w(&self) -> Vec3<f32> {
+        self.axis[2]
+    }
+
+    #[inline(always)]
+    pub fn to_local(self, a: Vec3<f32>) -> Vec3<f32> {
+        Vec3::new(a.dot(self.u()), a.dot(self.v()), a.dot(self.w()))
+    }
+
+    #[inline(always)]
+    pub fn to_world(self, a: Vec3<f32>) -> Vec3<f32> {
+        self.u() * a.x() + self.v() * a.y() + self.w() * a.z()
+    }
+}
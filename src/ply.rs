@@ -0,0 +1,414 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use crate::{mesh::MeshData, vec3::Vec3};
+
+// stanford polygon files, ascii or binary in either byte order
+// http://paulbourke.net/dataformats/ply/
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(&format!("unknown ply type {name}"))),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // what an integer color channel is divided by to land in [0, 1]
+    fn color_scale(&self) -> f32 {
+        match self {
+            Scalar::U8 | Scalar::I8 => 255.0,
+            Scalar::U16 | Scalar::I16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    // count type, item type
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// the data section, read one value at a time whatever the format
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64> {
+        if self.format == Format::Ascii {
+            let rest = &self.bytes[self.pos..];
+            let start = rest
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .ok_or_else(|| invalid("truncated ply data"))?;
+            let len = rest[start..]
+                .iter()
+                .position(|b| b.is_ascii_whitespace())
+                .unwrap_or(rest.len() - start);
+            self.pos += start + len;
+            let token = std::str::from_utf8(&rest[start..start + len])
+                .map_err(|_| invalid("bad ply value"))?;
+            return token.parse::<f64>().map_err(|_| invalid("bad ply value"));
+        }
+
+        let size = scalar.size();
+        let raw = self
+            .bytes
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| invalid("truncated ply data"))?;
+        self.pos += size;
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(raw);
+        if self.format == Format::BinaryBigEndian {
+            b[..size].reverse();
+        }
+        let b4 = [b[0], b[1], b[2], b[3]];
+        Ok(match scalar {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes(b4) as f64,
+            Scalar::U32 => u32::from_le_bytes(b4) as f64,
+            Scalar::F32 => f32::from_le_bytes(b4) as f64,
+            Scalar::F64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+// positions, normals (nx ny nz), colors (red green blue) and uvs (u v, s t or
+// texture_u texture_v) of the vertex element and the polygons of the face element, fanned
+// into triangles. integer colors are taken as gamma 2 encoded like the images we write
+pub fn load(path: impl AsRef<Path>) -> Result<MeshData> {
+    read(&fs::read(path)?)
+}
+
+pub fn read(bytes: &[u8]) -> Result<MeshData> {
+    let (format, elements, body_start) = read_header(bytes)?;
+    let mut body = Body {
+        format,
+        bytes,
+        pos: body_start,
+    };
+
+    let mut mesh = MeshData::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut body, element, &mut mesh)?,
+            "face" => read_faces(&mut body, element, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    read_row(&mut body, element, |_, _| {}, |_, _| {})?;
+                }
+            }
+        }
+    }
+    if mesh.positions.is_empty() || mesh.triangles.is_empty() {
+        return Err(invalid("ply has no triangles"));
+    }
+    Ok(mesh)
+}
+
+fn read_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize)> {
+    let end = bytes
+        .windows(10)
+        .position(|w| w == b"end_header")
+        .ok_or_else(|| invalid("missing ply end_header"))?;
+    // the data starts after the end_header line break
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |p| end + p + 1);
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| invalid("bad ply header"))?;
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid("missing ply magic"));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, _] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid("unknown ply format")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid("bad ply element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("ply property before any element"))?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    Scalar::parse(count)?,
+                    Scalar::parse(item)?,
+                )),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("ply property before any element"))?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::parse(ty)?)),
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| invalid("missing ply format"))?;
+    Ok((format, elements, body_start))
+}
+
+// reads one row, handing scalars and lists to the callbacks by property index
+fn read_row(
+    body: &mut Body,
+    element: &Element,
+    mut scalar: impl FnMut(usize, f64),
+    mut list: impl FnMut(usize, &[f64]),
+) -> Result<()> {
+    let mut items = Vec::new();
+    for (i, property) in element.properties.iter().enumerate() {
+        match property {
+            Property::Scalar(_, ty) => scalar(i, body.read(*ty)?),
+            Property::List(_, count_ty, item_ty) => {
+                let count = body.read(*count_ty)? as usize;
+                items.clear();
+                for _ in 0..count {
+                    items.push(body.read(*item_ty)?);
+                }
+                list(i, &items);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_vertices(body: &mut Body, element: &Element, mesh: &mut MeshData) -> Result<()> {
+    let find = |names: &[&str]| {
+        element.properties.iter().position(|p| match p {
+            Property::Scalar(name, _) => names.contains(&name.as_str()),
+            _ => false,
+        })
+    };
+    let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+    let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+    let color = [
+        find(&["red", "r", "diffuse_red"]),
+        find(&["green", "g", "diffuse_green"]),
+        find(&["blue", "b", "diffuse_blue"]),
+    ];
+    let uv = [
+        find(&["u", "s", "texture_u", "texture_s"]),
+        find(&["v", "t", "texture_v", "texture_t"]),
+    ];
+    let [Some(px), Some(py), Some(pz)] = position else {
+        return Err(invalid("ply vertices need x, y and z"));
+    };
+    let has_normals = normal.iter().all(Option::is_some);
+    let has_colors = color.iter().all(Option::is_some);
+    let has_uvs = uv.iter().all(Option::is_some);
+    let color_scale = match color[0].map(|i| &element.properties[i]) {
+        Some(Property::Scalar(_, ty)) => ty.color_scale(),
+        _ => 1.0,
+    };
+
+    let mut row = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        read_row(body, element, |i, v| row[i] = v, |_, _| {})?;
+        let get = |i: Option<usize>| i.map_or(0.0, |i| row[i] as f32);
+        mesh.positions
+            .push(Vec3::new(row[px] as f32, row[py] as f32, row[pz] as f32));
+        if has_normals {
+            mesh.normals
+                .push(Vec3::new(get(normal[0]), get(normal[1]), get(normal[2])));
+        }
+        if has_colors {
+            let c = |i| {
+                let v = get(i) / color_scale;
+                if color_scale > 1.0 {
+                    v * v
+                } else {
+                    v
+                }
+            };
+            mesh.colors
+                .push(Vec3::new(c(color[0]), c(color[1]), c(color[2])));
+        }
+        if has_uvs {
+            mesh.uvs.push((get(uv[0]), get(uv[1])));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(body: &mut Body, element: &Element, mesh: &mut MeshData) -> Result<()> {
+    let indices = element.properties.iter().position(|p| match p {
+        Property::List(name, _, _) => name == "vertex_indices" || name == "vertex_index",
+        _ => false,
+    });
+    let Some(indices) = indices else {
+        return Err(invalid("ply faces need vertex_indices"));
+    };
+    for _ in 0..element.count {
+        read_row(
+            body,
+            element,
+            |_, _| {},
+            |i, polygon| {
+                if i != indices || polygon.len() < 3 {
+                    return;
+                }
+                for k in 1..polygon.len() - 1 {
+                    mesh.triangles.push([
+                        polygon[0] as usize,
+                        polygon[k] as usize,
+                        polygon[k + 1] as usize,
+                    ]);
+                }
+            },
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xyz(v: Vec3<f32>) -> [f32; 3] {
+        [v.x(), v.y(), v.z()]
+    }
+
+    #[test]
+    fn ascii_quad_is_fanned() {
+        let ply = b"ply\nformat ascii 1.0\ncomment a unit square\nelement vertex 4\n\
+            property float x\nproperty float y\nproperty float z\n\
+            property float nx\nproperty float ny\nproperty float nz\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            0 0 0 0 0 1\n1 0 0 0 0 1\n1 1 0 0 0 1\n0 1 0 0 0 1\n4 0 1 2 3\n";
+        let mesh = read(ply).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(xyz(mesh.positions[2]), [1.0, 1.0, 0.0]);
+        assert_eq!(xyz(mesh.normals[3]), [0.0, 0.0, 1.0]);
+        assert!(mesh.colors.is_empty() && mesh.uvs.is_empty());
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    // a triangle with uchar colors, the face list also carries an ignored property
+    fn binary_triangle(
+        format: &str,
+        int: fn(i32) -> [u8; 4],
+        float: fn(f32) -> [u8; 4],
+    ) -> Vec<u8> {
+        let mut ply = format!(
+            "ply\nformat {format} 1.0\nelement vertex 3\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face 1\nproperty list uchar int vertex_indices\nproperty uchar flags\n\
+             end_header\n"
+        )
+        .into_bytes();
+        let vertices = [
+            ([0.0, 0.0, 0.0], [255, 0, 0]),
+            ([2.0, 0.0, 0.0], [0, 255, 0]),
+            ([0.0, 3.0, -1.0], [0, 0, 255]),
+        ];
+        for (p, c) in vertices {
+            for v in p {
+                ply.extend(float(v));
+            }
+            ply.extend(c);
+        }
+        ply.push(3);
+        for i in [0, 1, 2] {
+            ply.extend(int(i));
+        }
+        ply.push(7);
+        ply
+    }
+
+    fn check_binary_triangle(mesh: MeshData) {
+        assert_eq!(xyz(mesh.positions[1]), [2.0, 0.0, 0.0]);
+        assert_eq!(xyz(mesh.positions[2]), [0.0, 3.0, -1.0]);
+        assert_eq!(xyz(mesh.colors[0]), [1.0, 0.0, 0.0]);
+        assert_eq!(xyz(mesh.colors[2]), [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        let ply = binary_triangle("binary_little_endian", i32::to_le_bytes, f32::to_le_bytes);
+        check_binary_triangle(read(&ply).unwrap());
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        let ply = binary_triangle("binary_big_endian", i32::to_be_bytes, f32::to_be_bytes);
+        check_binary_triangle(read(&ply).unwrap());
+    }
+
+    #[test]
+    fn truncated_body_fails() {
+        let ply = binary_triangle("binary_little_endian", i32::to_le_bytes, f32::to_le_bytes);
+        assert!(read(&ply[..ply.len() - 3]).is_err());
+    }
+}
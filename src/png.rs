@@ -0,0 +1,481 @@
+use std::io::{Error, ErrorKind, Result};
+
+use crate::{hdr::HdrImage, vec3::Vec3};
+
+// png images for textures. values come back as stored, scaled to [0, 1] without any
+// gamma decoding, alpha is dropped. interlaced images aren't supported
+// https://www.w3.org/TR/png/
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+pub fn read(bytes: &[u8]) -> Result<HdrImage> {
+    if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Err(invalid("not a png file"));
+    }
+    let mut pos = 8;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut compressed = Vec::new();
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+            as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+        let data = bytes
+            .get(pos + 8..pos + 8 + len)
+            .ok_or_else(|| invalid("truncated png chunk"))?;
+        // chunk data is followed by a crc we don't check
+        pos += 12 + len;
+        match kind {
+            b"IHDR" => header = Some(Header::parse(data)?),
+            b"PLTE" => palette = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let header = header.ok_or_else(|| invalid("missing png header"))?;
+    if compressed.len() < 2 {
+        return Err(invalid("missing png image data"));
+    }
+    // two bytes of zlib header in front of the deflate stream, adler32 behind it
+    let raw = inflate(&compressed[2..])?;
+    let pixels = header.unfilter(&raw)?;
+
+    let channels = header.channels();
+    let max = ((1u32 << header.depth) - 1) as f32;
+    let mut data = Vec::with_capacity(header.width * header.height);
+    for row in pixels.chunks_exact(header.row_bytes()) {
+        for x in 0..header.width {
+            let sample = |c: usize| header.sample(row, x * channels + c);
+            data.push(match header.color_type {
+                // gray and gray + alpha
+                0 | 4 => {
+                    let g = sample(0) as f32 / max;
+                    Vec3::new(g, g, g)
+                }
+                // rgb and rgba
+                2 | 6 => Vec3::new(
+                    sample(0) as f32 / max,
+                    sample(1) as f32 / max,
+                    sample(2) as f32 / max,
+                ),
+                // palette
+                _ => {
+                    let i = sample(0) as usize * 3;
+                    let entry = palette
+                        .get(i..i + 3)
+                        .ok_or_else(|| invalid("png palette index out of range"))?;
+                    Vec3::new(
+                        entry[0] as f32 / 255.0,
+                        entry[1] as f32 / 255.0,
+                        entry[2] as f32 / 255.0,
+                    )
+                }
+            });
+        }
+    }
+    Ok(HdrImage {
+        width: header.width,
+        height: header.height,
+        data,
+    })
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u32,
+    color_type: u8,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 13 {
+            return Err(invalid("truncated png header"));
+        }
+        let be = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let header = Self {
+            width: be(0) as usize,
+            height: be(4) as usize,
+            depth: data[8] as u32,
+            color_type: data[9],
+        };
+        let depth_ok = match header.color_type {
+            0 => matches!(header.depth, 1 | 2 | 4 | 8 | 16),
+            3 => matches!(header.depth, 1 | 2 | 4 | 8),
+            2 | 4 | 6 => matches!(header.depth, 8 | 16),
+            _ => return Err(invalid("unknown png color type")),
+        };
+        if !depth_ok {
+            return Err(invalid("bad png bit depth"));
+        }
+        if data[12] != 0 {
+            return Err(invalid("interlaced png is not supported"));
+        }
+        if header.width == 0 || header.height == 0 {
+            return Err(invalid("empty png image"));
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    fn row_bytes(&self) -> usize {
+        (self.width * self.channels() * self.depth as usize).div_ceil(8)
+    }
+
+    // i-th sample of a row, samples below 8 bits are packed from the high bit down
+    fn sample(&self, row: &[u8], i: usize) -> u32 {
+        match self.depth {
+            16 => u16::from_be_bytes([row[i * 2], row[i * 2 + 1]]) as u32,
+            8 => row[i] as u32,
+            d => {
+                let bit = i * d as usize;
+                let shift = 8 - d as usize - bit % 8;
+                (row[bit / 8] as u32 >> shift) & ((1 << d) - 1)
+            }
+        }
+    }
+
+    // undoes the per row prediction, returns the rows without their filter bytes
+    fn unfilter(&self, raw: &[u8]) -> Result<Vec<u8>> {
+        let stride = self.row_bytes();
+        if raw.len() < (stride + 1) * self.height {
+            return Err(invalid("truncated png image data"));
+        }
+        // distance to the corresponding byte of the previous pixel
+        let bpp = (self.channels() * self.depth as usize).div_ceil(8);
+        let mut out = vec![0u8; stride * self.height];
+        for y in 0..self.height {
+            let filter = raw[y * (stride + 1)];
+            let src = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+            let (done, rest) = out.split_at_mut(y * stride);
+            let prior = if y > 0 {
+                &done[(y - 1) * stride..]
+            } else {
+                &[][..]
+            };
+            let row = &mut rest[..stride];
+            for i in 0..stride {
+                let a = if i >= bpp { row[i - bpp] } else { 0 };
+                let b = prior.get(i).copied().unwrap_or(0);
+                let c = if i >= bpp {
+                    prior.get(i - bpp).copied().unwrap_or(0)
+                } else {
+                    0
+                };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    4 => paeth(a, b, c),
+                    _ => return Err(invalid("bad png filter")),
+                };
+                row[i] = src[i].wrapping_add(predicted);
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// deflate decoder, in the manner of zlib's puff.c
+// https://www.rfc-editor.org/rfc/rfc1951
+
+struct Bits<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn need(&mut self, n: u32) -> Result<u32> {
+        while self.count < n {
+            let b = *self
+                .bytes
+                .get(self.pos)
+                .ok_or_else(|| invalid("truncated deflate stream"))?;
+            self.pos += 1;
+            self.buf |= (b as u32) << self.count;
+            self.count += 8;
+        }
+        let v = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(v)
+    }
+}
+
+// canonical huffman code, symbols sorted by code length
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for l in 1..15 {
+            offsets[l + 1] = offsets[l] + counts[l];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = symbol as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    // codes are stored most significant bit first, one bit at a time
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.need(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return self
+                    .symbols
+                    .get((index + code - first) as usize)
+                    .copied()
+                    .ok_or_else(|| invalid("bad deflate code"));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad deflate code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+fn inflate(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut bits = Bits {
+        bytes,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.need(1)?;
+        match bits.need(2)? {
+            0 => {
+                // stored block, byte aligned
+                bits.buf = 0;
+                bits.count = 0;
+                let header = bytes
+                    .get(bits.pos..bits.pos + 4)
+                    .ok_or_else(|| invalid("truncated deflate stream"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if len != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err(invalid("bad stored deflate block"));
+                }
+                let data = bytes
+                    .get(bits.pos + 4..bits.pos + 4 + len)
+                    .ok_or_else(|| invalid("truncated deflate stream"))?;
+                out.extend_from_slice(data);
+                bits.pos += 4 + len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let lit = Huffman::new(&lengths);
+                let dist = Huffman::new(&[5; 30]);
+                codes(&mut bits, &mut out, &lit, &dist)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_tables(&mut bits)?;
+                codes(&mut bits, &mut out, &lit, &dist)?;
+            }
+            _ => return Err(invalid("bad deflate block type")),
+        }
+        if last == 1 {
+            return Ok(out);
+        }
+    }
+}
+
+fn dynamic_tables(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    let nlen = bits.need(5)? as usize + 257;
+    let ndist = bits.need(5)? as usize + 1;
+    let ncode = bits.need(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &i in &ORDER[..ncode] {
+        code_lengths[i] = bits.need(3)? as u8;
+    }
+    let lencode = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < nlen + ndist {
+        let symbol = lencode.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let prev = *lengths[..i]
+                    .last()
+                    .ok_or_else(|| invalid("deflate repeat with no previous length"))?;
+                (prev, 3 + bits.need(2)? as usize)
+            }
+            17 => (0, 3 + bits.need(3)? as usize),
+            _ => (0, 11 + bits.need(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid("too many deflate code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    Ok((
+        Huffman::new(&lengths[..nlen]),
+        Huffman::new(&lengths[nlen..]),
+    ))
+}
+
+fn codes(bits: &mut Bits, out: &mut Vec<u8>, lit: &Huffman, dist: &Huffman) -> Result<()> {
+    loop {
+        let symbol = lit.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(invalid("bad deflate length"));
+                }
+                let len = LENGTH_BASE[i] as usize + bits.need(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = dist.decode(bits)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err(invalid("bad deflate distance"));
+                }
+                let back = DIST_BASE[d] as usize + bits.need(DIST_EXTRA[d] as u32)? as usize;
+                if back > out.len() {
+                    return Err(invalid("deflate distance too far back"));
+                }
+                // may overlap what it is copying, go byte by byte
+                let start = out.len() - back;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the streams are raw deflate from zlib
+
+    #[test]
+    fn stored_block() {
+        let stream = [
+            1, 12, 0, 243, 255, 115, 116, 111, 114, 101, 100, 32, 98, 108, 111, 99, 107,
+        ];
+        assert_eq!(inflate(&stream).unwrap(), b"stored block");
+    }
+
+    #[test]
+    fn fixed_huffman_block() {
+        let stream = [
+            75, 76, 74, 78, 132, 33, 133, 140, 212, 156, 156, 124, 8, 9, 0,
+        ];
+        assert_eq!(inflate(&stream).unwrap(), b"abcabcabcabc hello hello");
+    }
+
+    #[test]
+    fn dynamic_huffman_block() {
+        let stream = [
+            37, 138, 193, 17, 0, 0, 12, 193, 102, 69, 247, 159, 161, 210, 122, 144, 203, 145, 147,
+            168, 105, 89, 71, 3, 86, 154, 105, 15, 210, 44, 230, 95, 70, 45,
+        ];
+        assert_eq!(
+            inflate(&stream).unwrap(),
+            b"abcccaaaacaabacaaaadcaabccabaabcabadaaaabbadabaababacaabaaab"
+        );
+    }
+
+    #[test]
+    fn copies_reach_back_across_blocks() {
+        // fixed, empty stored from a sync flush, then fixed again
+        let stream = [
+            202, 72, 205, 201, 201, 87, 200, 0, 147, 0, 0, 0, 0, 255, 255, 3, 147, 0,
+        ];
+        assert_eq!(inflate(&stream).unwrap(), b"hello hello hello");
+    }
+
+    #[test]
+    fn truncated_stream_fails() {
+        let stream = [75, 76, 74, 78, 132, 33, 133, 140, 212, 156];
+        assert!(inflate(&stream).is_err());
+    }
+
+    #[test]
+    fn canonical_codes() {
+        // lengths 2, 1, 3, 3 give the codes 10, 0, 110 and 111
+        let code = Huffman::new(&[2, 1, 3, 3]);
+        let mut bits = Bits {
+            bytes: &[0b1101_1010, 0b1],
+            pos: 0,
+            buf: 0,
+            count: 0,
+        };
+        let symbols: Vec<u16> = (0..4).map(|_| code.decode(&mut bits).unwrap()).collect();
+        assert_eq!(symbols, [1, 0, 2, 3]);
+    }
+}
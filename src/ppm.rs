@@ -22,30 +22,33 @@ impl Ppm {
         }
     }
 
+    // fails if the file exists, finished renders never replace one
     pub fn write(self, filename: &str) -> Result<()> {
-        let mut row = 0;
-        let header = format!(
+        File::create_new(filename)?.write_all(self.contents().as_bytes())
+    }
+
+    // replaces the file, for previews rewritten while a render runs
+    pub fn overwrite(self, filename: &str) -> Result<()> {
+        File::create(filename)?.write_all(self.contents().as_bytes())
+    }
+
+    fn contents(&self) -> String {
+        format!(
             "P3\n{} {}\n{}\n{}",
             self.width,
             self.height,
             self.max_color_value,
             self.data
                 .chunks(3 * self.width)
-                .map(|x| {
-                    row += 1;
-                    println!("Output {}/{}", row, self.height);
-                    format!(
-                        "{}\n",
-                        x.iter()
-                            .map(|c| c.to_string())
-                            .collect::<Vec<String>>()
-                            .join(" ")
-                    )
-                })
+                .map(|x| format!(
+                    "{}\n",
+                    x.iter()
+                        .map(|c| c.to_string())
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                ))
                 .collect::<Vec<String>>()
                 .join("")
-        );
-        File::create_new(filename)?.write_all(header.as_bytes())?;
-        Ok(())
+        )
     }
 }
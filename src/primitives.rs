@@ -0,0 +1,873 @@
+use std::{f32::consts::PI, rc::Rc};
+
+use crate::{
+    aabb::Aabb,
+    instance::Instance,
+    interval::{Interval, INTERVAL_UNIVERSE},
+    mat4::{Mat4, Quat},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    vec3::Vec3,
+    HitRecord, Hittable,
+};
+
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+macro_rules! unit_v {
+    ($v:expr) => {
+        $v / f32_len!($v.length_squared())
+    };
+}
+
+/// Sphere with uv coordinates from [`Sphere::get_sphere_uv`].
+pub struct Sphere {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+    pub material: Rc<Box<dyn Material>>,
+}
+
+impl Sphere {
+    // p is a point on the unit sphere, u is the angle around the y axis from x = -1,
+    // v is the angle from y = -1 to y = +1
+    pub fn get_sphere_uv(p: Vec3<f32>) -> (f32, f32) {
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+}
+
+// shared by static and moving spheres
+pub(crate) fn hit_sphere(
+    center: Vec3<f32>,
+    radius: f32,
+    r: &Ray<f32>,
+    ray_t: Interval,
+    rec: &mut HitRecord,
+    material: Rc<Box<dyn Material>>,
+) -> bool {
+    let oc = center - r.origin();
+    let a = r.direction().length_squared();
+    let h = r.direction().dot(oc);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = h * h - a * c;
+    if discriminant < 0.0 {
+        return false;
+    }
+
+    let sqrtd = f32_len!(discriminant);
+    let mut root = (h - sqrtd) / a;
+    if !ray_t.surrounds(root) {
+        root = (h + sqrtd) / a;
+        if !ray_t.surrounds(root) {
+            return false;
+        }
+    }
+
+    let p = r.at(root);
+    let outward_normal = (p - center) / radius;
+    let (u, v) = Sphere::get_sphere_uv(outward_normal);
+    *rec = HitRecord {
+        p,
+        normal: outward_normal,
+        t: root,
+        u,
+        v,
+        front_face: false,
+        material: Some(material),
+        color: None,
+    };
+    rec.set_face_normal(r, rec.normal);
+    true
+}
+
+impl Hittable for Sphere {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        hit_sphere(self.center, self.radius, r, ray_t, rec, material)
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(self.center - r, self.center + r)
+    }
+}
+
+// parallelogram with corner q and edges u, v
+pub struct Quad {
+    pub q: Vec3<f32>,
+    pub u: Vec3<f32>,
+    pub v: Vec3<f32>,
+    pub material: Rc<Box<dyn Material>>,
+    normal: Vec3<f32>,
+    d: f32,
+    w: Vec3<f32>,
+}
+
+impl Quad {
+    pub fn new(q: Vec3<f32>, u: Vec3<f32>, v: Vec3<f32>, material: Rc<Box<dyn Material>>) -> Self {
+        let n = u.cross(v);
+        let normal = unit_v!(n);
+        Self {
+            q,
+            u,
+            v,
+            material,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+        }
+    }
+
+    // ray parameter and (alpha, beta) edge coordinates of the hit
+    pub fn intersect(&self, r: &Ray<f32>, ray_t: &Interval) -> Option<(f32, f32, f32)> {
+        let denom = self.normal.dot(r.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return None;
+        }
+        let planar = r.at(t) - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((t, alpha, beta))
+    }
+
+    pub fn normal(&self) -> Vec3<f32> {
+        self.normal
+    }
+
+    pub fn area(&self) -> f32 {
+        f32_len!(self.u.cross(self.v).length_squared())
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let Some((t, alpha, beta)) = self.intersect(r, &ray_t) else {
+            return false;
+        };
+        *rec = HitRecord {
+            p: r.at(t),
+            normal: self.normal,
+            t,
+            u: alpha,
+            v: beta,
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, self.normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let diagonals = Aabb::enclosing(
+            &Aabb::from_points(self.q, self.q + self.u + self.v),
+            &Aabb::from_points(self.q + self.u, self.q + self.v),
+        );
+        diagonals.padded()
+    }
+}
+
+// quadratic roots in increasing order, None without real roots
+fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // avoids cancellation when b is large
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+fn eval_poly(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().fold(0.0, |acc, &c| acc * x + c)
+}
+
+// real roots inside [lo, hi] of a polynomial with coefficients from the highest power
+// down. the derivative's roots split the interval into monotonic pieces, each holds at
+// most one root which bisection finds
+fn poly_roots(coeffs: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let degree = coeffs.len() - 1;
+    if degree == 1 {
+        let x = -coeffs[1] / coeffs[0];
+        return if coeffs[0] != 0.0 && (lo..=hi).contains(&x) {
+            vec![x]
+        } else {
+            vec![]
+        };
+    }
+    let derivative: Vec<f64> = coeffs[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| c * (degree - i) as f64)
+        .collect();
+    let mut bounds = vec![lo];
+    bounds.extend(poly_roots(&derivative, lo, hi));
+    bounds.push(hi);
+
+    let mut roots = Vec::new();
+    for w in bounds.windows(2) {
+        let (mut a, mut b) = (w[0], w[1]);
+        let (fa, fb) = (eval_poly(coeffs, a), eval_poly(coeffs, b));
+        if fa == 0.0 {
+            roots.push(a);
+            continue;
+        }
+        if fa.signum() == fb.signum() {
+            continue;
+        }
+        for _ in 0..60 {
+            let m = 0.5 * (a + b);
+            if eval_poly(coeffs, m).signum() == fa.signum() {
+                a = m;
+            } else {
+                b = m;
+            }
+        }
+        roots.push(0.5 * (a + b));
+    }
+    roots
+}
+
+// moller-trumbore, the ray parameter and barycentrics of v1 and v2 at the hit
+pub fn intersect_triangle(
+    r: &Ray<f32>,
+    v0: Vec3<f32>,
+    v1: Vec3<f32>,
+    v2: Vec3<f32>,
+    ray_t: &Interval,
+) -> Option<(f32, f32, f32)> {
+    let e1 = v1 - v0;
+    let e2 = v2 - v0;
+    let pvec = r.direction().cross(e2);
+    let det = e1.dot(pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = r.origin() - v0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(e1);
+    let b2 = r.direction().dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = e2.dot(qvec) * inv_det;
+    ray_t.surrounds(t).then_some((t, b1, b2))
+}
+
+// u around the axis, from the local x axis
+fn azimuth_u(x: f32, y: f32) -> f32 {
+    (y.atan2(x) / (2.0 * PI)).rem_euclid(1.0)
+}
+
+// moves rays into a frame with the shape's axis along z, t keeps its meaning
+fn to_local(frame: &Onb, origin: Vec3<f32>, r: &Ray<f32>) -> Ray<f32> {
+    Ray::with_time(
+        frame.to_local(r.origin() - origin),
+        frame.to_local(r.direction()),
+        r.time(),
+    )
+}
+
+// box around a disk of the given radius, lying in the plane with normal n
+fn disk_bounds(center: Vec3<f32>, n: Vec3<f32>, radius: f32) -> Aabb {
+    let extent = |c: f32| radius * (1.0 - c * c).max(0.0).sqrt();
+    let e = Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()));
+    Aabb::from_points(center - e, center + e).padded()
+}
+
+// infinite plane through point. u and v are distances along the plane's tangents
+pub struct Plane {
+    pub point: Vec3<f32>,
+    pub material: Rc<Box<dyn Material>>,
+    frame: Onb,
+}
+
+impl Plane {
+    pub fn new(point: Vec3<f32>, normal: Vec3<f32>, material: Rc<Box<dyn Material>>) -> Self {
+        Self {
+            point,
+            material,
+            frame: Onb::new(normal / normal.length_squared().sqrt()),
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let normal = self.frame.w();
+        let denom = normal.dot(r.direction());
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+        let t = (self.point - r.origin()).dot(normal) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+        let p = r.at(t);
+        let local = self.frame.to_local(p - self.point);
+        *rec = HitRecord {
+            p,
+            normal,
+            t,
+            u: local.x(),
+            v: local.y(),
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb {
+            x: INTERVAL_UNIVERSE,
+            y: INTERVAL_UNIVERSE,
+            z: INTERVAL_UNIVERSE,
+        }
+    }
+}
+
+// flat disk, u goes around the center and v outwards from it
+pub struct Disk {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+    pub material: Rc<Box<dyn Material>>,
+    frame: Onb,
+}
+
+impl Disk {
+    pub fn new(
+        center: Vec3<f32>,
+        normal: Vec3<f32>,
+        radius: f32,
+        material: Rc<Box<dyn Material>>,
+    ) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+            frame: Onb::new(normal / normal.length_squared().sqrt()),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let local = to_local(&self.frame, self.center, r);
+        if local.direction().z().abs() < 1e-8 {
+            return false;
+        }
+        let t = -local.origin().z() / local.direction().z();
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+        let lp = local.at(t);
+        let dist2 = lp.x() * lp.x() + lp.y() * lp.y();
+        if dist2 > self.radius * self.radius {
+            return false;
+        }
+        let normal = self.frame.w();
+        *rec = HitRecord {
+            p: r.at(t),
+            normal,
+            t,
+            u: azimuth_u(lp.x(), lp.y()),
+            v: dist2.sqrt() / self.radius,
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        disk_bounds(self.center, self.frame.w(), self.radius)
+    }
+}
+
+// axis aligned box between two corners, uv covers each face
+pub struct AxisBox {
+    pub bounds: Aabb,
+    pub material: Rc<Box<dyn Material>>,
+}
+
+impl AxisBox {
+    pub fn new(a: Vec3<f32>, b: Vec3<f32>, material: Rc<Box<dyn Material>>) -> Self {
+        Self {
+            bounds: Aabb::from_points(a, b),
+            material,
+        }
+    }
+}
+
+impl Hittable for AxisBox {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let Some(span) = self.bounds.hit(r, INTERVAL_UNIVERSE) else {
+            return false;
+        };
+        let t = if ray_t.surrounds(span.min) {
+            span.min
+        } else if ray_t.surrounds(span.max) {
+            span.max
+        } else {
+            return false;
+        };
+
+        let p = r.at(t);
+        let (lo, hi) = (self.bounds.min(), self.bounds.max());
+        let size = hi - lo;
+        let local = p - lo;
+        // the face is on the axis where p sits closest to a side, relative to the size
+        let (axis, positive) = (0..3)
+            .flat_map(|i| [(i, false), (i, true)])
+            .min_by(|&(i, pi), &(j, pj)| {
+                let d = |k: usize, pos: bool| {
+                    let x = local[k] / size[k].max(1e-8);
+                    if pos {
+                        (1.0 - x).abs()
+                    } else {
+                        x.abs()
+                    }
+                };
+                d(i, pi).total_cmp(&d(j, pj))
+            })
+            .unwrap();
+        let sign = if positive { 1.0 } else { -1.0 };
+        let outward_normal = match axis {
+            0 => Vec3::new(sign, 0.0, 0.0),
+            1 => Vec3::new(0.0, sign, 0.0),
+            _ => Vec3::new(0.0, 0.0, sign),
+        };
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        *rec = HitRecord {
+            p,
+            normal: outward_normal,
+            t,
+            u: (local[a] / size[a].max(1e-8)).clamp(0.0, 1.0),
+            v: (local[b] / size[b].max(1e-8)).clamp(0.0, 1.0),
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, outward_normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds.padded()
+    }
+}
+
+// box of the given size centered on center, turned by rotation. it's an axis aligned
+// box in its own space placed with an instance
+pub fn oriented_box(
+    center: Vec3<f32>,
+    size: Vec3<f32>,
+    rotation: Quat,
+    material: Rc<Box<dyn Material>>,
+) -> Instance {
+    let half = size * 0.5;
+    let local = AxisBox::new(-half, half, material);
+    Instance::new(Rc::new(local), Mat4::translate(center) * rotation.to_mat4())
+}
+
+// closed cylinder standing on base along axis. the side has u around and v up, the caps
+// use the disk mapping
+pub struct Cylinder {
+    pub base: Vec3<f32>,
+    pub radius: f32,
+    pub height: f32,
+    pub material: Rc<Box<dyn Material>>,
+    frame: Onb,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Vec3<f32>,
+        axis: Vec3<f32>,
+        radius: f32,
+        height: f32,
+        material: Rc<Box<dyn Material>>,
+    ) -> Self {
+        Self {
+            base,
+            radius,
+            height,
+            material,
+            frame: Onb::new(axis / axis.length_squared().sqrt()),
+        }
+    }
+}
+
+// closest local hit among the candidates: (t, local normal, u, v)
+type LocalHit = (f32, Vec3<f32>, f32, f32);
+
+fn closest(hits: impl Iterator<Item = LocalHit>, ray_t: &Interval) -> Option<LocalHit> {
+    hits.filter(|h| ray_t.surrounds(h.0))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+// hit with a cap disk at height z facing along normal_z
+fn cap_hit(local: &Ray<f32>, z: f32, radius: f32, normal_z: f32) -> Option<LocalHit> {
+    let dz = local.direction().z();
+    if dz.abs() < 1e-8 {
+        return None;
+    }
+    let t = (z - local.origin().z()) / dz;
+    let p = local.at(t);
+    let dist2 = p.x() * p.x() + p.y() * p.y();
+    (dist2 <= radius * radius).then(|| {
+        (
+            t,
+            Vec3::new(0.0, 0.0, normal_z),
+            azimuth_u(p.x(), p.y()),
+            dist2.sqrt() / radius,
+        )
+    })
+}
+
+impl Hittable for Cylinder {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let local = to_local(&self.frame, self.base, r);
+        let (o, d) = (local.origin(), local.direction());
+        let mut side = Vec::with_capacity(2);
+        if let Some((t0, t1)) = solve_quadratic(
+            d.x() * d.x() + d.y() * d.y(),
+            2.0 * (o.x() * d.x() + o.y() * d.y()),
+            o.x() * o.x() + o.y() * o.y() - self.radius * self.radius,
+        ) {
+            for t in [t0, t1] {
+                let p = local.at(t);
+                if (0.0..=self.height).contains(&p.z()) {
+                    side.push((
+                        t,
+                        Vec3::new(p.x() / self.radius, p.y() / self.radius, 0.0),
+                        azimuth_u(p.x(), p.y()),
+                        p.z() / self.height,
+                    ));
+                }
+            }
+        }
+        let caps = [
+            cap_hit(&local, 0.0, self.radius, -1.0),
+            cap_hit(&local, self.height, self.radius, 1.0),
+        ];
+        let Some((t, n, u, v)) =
+            closest(side.into_iter().chain(caps.into_iter().flatten()), &ray_t)
+        else {
+            return false;
+        };
+        let outward_normal = self.frame.to_world(n);
+        *rec = HitRecord {
+            p: r.at(t),
+            normal: outward_normal,
+            t,
+            u,
+            v,
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, outward_normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let axis = self.frame.w();
+        Aabb::enclosing(
+            &disk_bounds(self.base, axis, self.radius),
+            &disk_bounds(self.base + axis * self.height, axis, self.radius),
+        )
+    }
+}
+
+// cone with its base disk on base, narrowing to the apex height along axis
+pub struct Cone {
+    pub base: Vec3<f32>,
+    pub radius: f32,
+    pub height: f32,
+    pub material: Rc<Box<dyn Material>>,
+    frame: Onb,
+}
+
+impl Cone {
+    pub fn new(
+        base: Vec3<f32>,
+        axis: Vec3<f32>,
+        radius: f32,
+        height: f32,
+        material: Rc<Box<dyn Material>>,
+    ) -> Self {
+        Self {
+            base,
+            radius,
+            height,
+            material,
+            frame: Onb::new(axis / axis.length_squared().sqrt()),
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let local = to_local(&self.frame, self.base, r);
+        let (o, d) = (local.origin(), local.direction());
+        // x^2 + y^2 = (k (h - z))^2
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let oz = self.height - o.z();
+        let mut side = Vec::with_capacity(2);
+        if let Some((t0, t1)) = solve_quadratic(
+            d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z(),
+            2.0 * (o.x() * d.x() + o.y() * d.y() + k2 * oz * d.z()),
+            o.x() * o.x() + o.y() * o.y() - k2 * oz * oz,
+        ) {
+            for t in [t0, t1] {
+                let p = local.at(t);
+                if (0.0..=self.height).contains(&p.z()) {
+                    let rho = (p.x() * p.x() + p.y() * p.y()).sqrt().max(1e-8);
+                    let n = Vec3::new(p.x() / rho, p.y() / rho, k);
+                    side.push((
+                        t,
+                        n / (1.0 + k2).sqrt(),
+                        azimuth_u(p.x(), p.y()),
+                        p.z() / self.height,
+                    ));
+                }
+            }
+        }
+        let cap = cap_hit(&local, 0.0, self.radius, -1.0);
+        let Some((t, n, u, v)) = closest(side.into_iter().chain(cap), &ray_t) else {
+            return false;
+        };
+        let outward_normal = self.frame.to_world(n);
+        *rec = HitRecord {
+            p: r.at(t),
+            normal: outward_normal,
+            t,
+            u,
+            v,
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, outward_normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let apex = self.base + self.frame.w() * self.height;
+        Aabb::enclosing(
+            &disk_bounds(self.base, self.frame.w(), self.radius),
+            &Aabb::from_points(apex, apex),
+        )
+    }
+}
+
+// ring around axis through center. u goes around the ring, v around the tube
+pub struct Torus {
+    pub center: Vec3<f32>,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: Rc<Box<dyn Material>>,
+    frame: Onb,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vec3<f32>,
+        axis: Vec3<f32>,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Rc<Box<dyn Material>>,
+    ) -> Self {
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+            frame: Onb::new(axis / axis.length_squared().sqrt()),
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let local = to_local(&self.frame, self.center, r);
+        // only look for roots where the ray crosses the bounding sphere, keeps the
+        // polynomial well conditioned far from the torus
+        let bound = self.major_radius + self.minor_radius;
+        let (o, d) = (local.origin(), local.direction());
+        let Some((s0, s1)) = solve_quadratic(
+            d.length_squared(),
+            2.0 * o.dot(d),
+            o.length_squared() - bound * bound,
+        ) else {
+            return false;
+        };
+        let lo = s0.max(ray_t.min);
+        let hi = s1.min(ray_t.max);
+        if lo >= hi {
+            return false;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2), in f64 since the quartic is touchy
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let (dx, dy, dz) = (d.x() as f64, d.y() as f64, d.z() as f64);
+        let big_r2 = (self.major_radius as f64).powi(2);
+        let small_r2 = (self.minor_radius as f64).powi(2);
+        let a = dx * dx + dy * dy + dz * dz;
+        let b = 2.0 * (ox * dx + oy * dy + oz * dz);
+        let c = ox * ox + oy * oy + oz * oz + big_r2 - small_r2;
+        let coeffs = [
+            a * a,
+            2.0 * a * b,
+            b * b + 2.0 * a * c - 4.0 * big_r2 * (dx * dx + dy * dy),
+            2.0 * b * c - 8.0 * big_r2 * (ox * dx + oy * dy),
+            c * c - 4.0 * big_r2 * (ox * ox + oy * oy),
+        ];
+        let Some(t) = poly_roots(&coeffs, lo as f64, hi as f64)
+            .into_iter()
+            .map(|t| t as f32)
+            .find(|&t| ray_t.surrounds(t))
+        else {
+            return false;
+        };
+
+        let p = local.at(t);
+        let rho = (p.x() * p.x() + p.y() * p.y()).sqrt().max(1e-8);
+        // direction from the closest point on the ring's center circle
+        let ring = Vec3::new(p.x() / rho, p.y() / rho, 0.0) * self.major_radius;
+        let n = (p - ring) / self.minor_radius;
+        let outward_normal = self.frame.to_world(n);
+        *rec = HitRecord {
+            p: r.at(t),
+            normal: outward_normal,
+            t,
+            u: azimuth_u(p.x(), p.y()),
+            v: (p.z().atan2(rho - self.major_radius) / (2.0 * PI)).rem_euclid(1.0),
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, outward_normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let axis = self.frame.w() * self.minor_radius;
+        let radius = self.major_radius + self.minor_radius;
+        Aabb::enclosing(
+            &disk_bounds(self.center - axis, self.frame.w(), radius),
+            &disk_bounds(self.center + axis, self.frame.w(), radius),
+        )
+    }
+}
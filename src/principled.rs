@@ -0,0 +1,320 @@
+use std::{f32::consts::PI, rc::Rc};
+
+use crate::{
+    material::{BsdfSample, LobeFlags, Material},
+    microfacet::{
+        fr_dielectric, reflect_local, refract_local, refraction_half_vector, TrowbridgeReitz,
+    },
+    onb::Onb,
+    random_double,
+    texture::{SolidColor, Texture},
+    vec3::{random_cosine_direction, Vec3},
+    HitRecord, Ray,
+};
+
+// disney "principled" bsdf, mostly following the 2012 and 2015 course notes
+// https://media.disneyanimation.com/uploads/production/publication_asset/48/asset/s2012_pbs_disney_brdf_notes_v3.pdf
+// scalar parameters are read from the first channel of their texture
+pub struct Principled {
+    pub base_color: Rc<dyn Texture>,
+    pub metallic: Rc<dyn Texture>,
+    pub roughness: Rc<dyn Texture>,
+    pub specular: Rc<dyn Texture>,
+    pub sheen: Rc<dyn Texture>,
+    pub clearcoat: Rc<dyn Texture>,
+    pub clearcoat_gloss: Rc<dyn Texture>,
+    pub transmission: Rc<dyn Texture>,
+    pub ior: f32,
+}
+
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+macro_rules! unit_v {
+    ($v:expr) => {
+        $v / f32_len!($v.length_squared())
+    };
+}
+
+impl Principled {
+    pub fn new(base_color: Rc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: SolidColor::scalar(0.0),
+            roughness: SolidColor::scalar(0.5),
+            specular: SolidColor::scalar(0.5),
+            sheen: SolidColor::scalar(0.0),
+            clearcoat: SolidColor::scalar(0.0),
+            clearcoat_gloss: SolidColor::scalar(1.0),
+            transmission: SolidColor::scalar(0.0),
+            ior: 1.5,
+        }
+    }
+
+    fn lobes(&self, rec: &HitRecord, wo: Vec3<f32>) -> Lobes {
+        let base = self.base_color.value(rec);
+        let metallic = self.metallic.value(rec).x().clamp(0.0, 1.0);
+        let roughness = self.roughness.value(rec).x().clamp(0.01, 1.0);
+        let specular = self.specular.value(rec).x().max(0.0);
+        let sheen = self.sheen.value(rec).x().max(0.0);
+        let clearcoat = self.clearcoat.value(rec).x().max(0.0);
+        let clearcoat_gloss = self.clearcoat_gloss.value(rec).x().clamp(0.0, 1.0);
+        let transmission = self.transmission.value(rec).x().clamp(0.0, 1.0);
+
+        let tint = if luminance(base) > 0.0 {
+            base / luminance(base)
+        } else {
+            Vec3::new(1.0, 1.0, 1.0)
+        };
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let spec0 = lerp(white * (specular * 0.08), base, metallic);
+
+        let diffuse = (1.0 - metallic) * (1.0 - transmission);
+        let transmission = (1.0 - metallic) * transmission;
+        let clearcoat = 0.25 * clearcoat;
+
+        // pick lobes roughly by how much energy they reflect towards wo
+        let fresnel_wo = luminance(spec0 + (white - spec0) * schlick_weight(wo.z()));
+        let mut prob = [
+            diffuse,
+            fresnel_wo.max(0.1) * (1.0 - transmission),
+            clearcoat,
+            transmission,
+        ];
+        let total: f32 = prob.iter().sum();
+        prob.iter_mut().for_each(|p| *p /= total);
+
+        Lobes {
+            base,
+            roughness,
+            spec0,
+            sheen_color: lerp(white, tint, 0.5) * sheen,
+            diffuse,
+            clearcoat,
+            transmission,
+            distribution: TrowbridgeReitz::from_roughness(roughness),
+            clearcoat_alpha: 0.1 * (1.0 - clearcoat_gloss) + 0.001 * clearcoat_gloss,
+            eta: if rec.front_face {
+                self.ior
+            } else {
+                1.0 / self.ior
+            },
+            prob,
+        }
+    }
+}
+
+// the bsdf at a single shading point, in the local frame
+struct Lobes {
+    base: Vec3<f32>,
+    roughness: f32,
+    spec0: Vec3<f32>,
+    sheen_color: Vec3<f32>,
+    diffuse: f32,
+    clearcoat: f32,
+    transmission: f32,
+    distribution: TrowbridgeReitz,
+    clearcoat_alpha: f32,
+    eta: f32,
+    // diffuse, specular, clearcoat, transmission
+    prob: [f32; 4],
+}
+
+#[inline(always)]
+fn luminance(c: Vec3<f32>) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+#[inline(always)]
+fn lerp(a: Vec3<f32>, b: Vec3<f32>, t: f32) -> Vec3<f32> {
+    a * (1.0 - t) + b * t
+}
+
+#[inline(always)]
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+// generalized trowbridge-reitz with gamma = 1, only used for the clearcoat
+fn gtr1(cos_theta_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * cos_theta_h * cos_theta_h;
+    (a2 - 1.0) / (PI * a2.ln() * t)
+}
+
+fn sample_gtr1(alpha: f32) -> Vec3<f32> {
+    let a2 = alpha * alpha;
+    let cos_theta = ((1.0 - a2.powf(1.0 - random_double())) / (1.0 - a2))
+        .max(0.0)
+        .sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * random_double();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// separable smith term with the 1 / (4 cos cos) folded in
+fn smith_g_ggx(cos_theta: f32, alpha: f32) -> f32 {
+    let a = alpha * alpha;
+    let b = cos_theta * cos_theta;
+    1.0 / (cos_theta + (a + b - a * b).sqrt())
+}
+
+impl Lobes {
+    fn eval(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> Vec3<f32> {
+        let mut f = Vec3::new(0.0, 0.0, 0.0);
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return f;
+        }
+
+        if wi.z() > 0.0 {
+            let wh = unit_v!(wo + wi);
+            let cos_d = wi.dot(wh);
+
+            if self.diffuse > 0.0 {
+                let fl = schlick_weight(wi.z());
+                let fv = schlick_weight(wo.z());
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+                f += self.base * (fd / PI * self.diffuse);
+                f += self.sheen_color * (schlick_weight(cos_d) * self.diffuse);
+            }
+
+            let white = Vec3::new(1.0, 1.0, 1.0);
+            let fresnel = (self.spec0 + (white - self.spec0) * schlick_weight(cos_d))
+                * (1.0 - self.transmission)
+                + white * (fr_dielectric(wo.dot(wh), self.eta) * self.transmission);
+            let spec =
+                self.distribution.d(wh) * self.distribution.g(wo, wi) / (4.0 * wo.z() * wi.z());
+            f += fresnel * spec;
+
+            if self.clearcoat > 0.0 {
+                let fresnel = 0.04 + 0.96 * schlick_weight(cos_d);
+                let coat = self.clearcoat
+                    * gtr1(wh.z(), self.clearcoat_alpha)
+                    * fresnel
+                    * smith_g_ggx(wi.z(), 0.25)
+                    * smith_g_ggx(wo.z(), 0.25);
+                f += Vec3::new(coat, coat, coat);
+            }
+        } else if self.transmission > 0.0 {
+            let Some(wh) = refraction_half_vector(wo, wi, self.eta) else {
+                return f;
+            };
+            let denom = (wi.dot(wh) + wo.dot(wh) / self.eta).powi(2);
+            let ft = self.distribution.d(wh)
+                * (1.0 - fr_dielectric(wo.dot(wh), self.eta))
+                * self.distribution.g(wo, wi)
+                * (wi.dot(wh) * wo.dot(wh) / (wi.z() * wo.z() * denom)).abs()
+                / (self.eta * self.eta);
+            let tint = Vec3::new(
+                self.base.x().sqrt(),
+                self.base.y().sqrt(),
+                self.base.z().sqrt(),
+            );
+            f += tint * (ft * self.transmission);
+        }
+        f
+    }
+
+    fn pdf(&self, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        let [p_diffuse, p_specular, p_clearcoat, p_transmission] = self.prob;
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+
+        if wi.z() > 0.0 {
+            let wh = unit_v!(wo + wi);
+            let reflect_pdf = self.distribution.d_visible(wo, wh) / (4.0 * wo.dot(wh));
+            let mut pdf = p_diffuse * wi.z() / PI + p_specular * reflect_pdf;
+            if p_clearcoat > 0.0 {
+                pdf +=
+                    p_clearcoat * gtr1(wh.z(), self.clearcoat_alpha) * wh.z() / (4.0 * wo.dot(wh));
+            }
+            if p_transmission > 0.0 {
+                pdf += p_transmission * reflect_pdf * fr_dielectric(wo.dot(wh), self.eta);
+            }
+            pdf
+        } else if p_transmission > 0.0 {
+            let Some(wh) = refraction_half_vector(wo, wi, self.eta) else {
+                return 0.0;
+            };
+            let denom = (wi.dot(wh) + wo.dot(wh) / self.eta).powi(2);
+            p_transmission * self.distribution.d_visible(wo, wh) * wi.dot(wh).abs() / denom
+                * (1.0 - fr_dielectric(wo.dot(wh), self.eta))
+        } else {
+            0.0
+        }
+    }
+
+    fn sample(&self, wo: Vec3<f32>) -> Option<Vec3<f32>> {
+        let [p_diffuse, p_specular, p_clearcoat, _] = self.prob;
+        let u = random_double();
+        let wi = if u < p_diffuse {
+            random_cosine_direction()
+        } else if u < p_diffuse + p_specular {
+            reflect_local(wo, self.distribution.sample_wm(wo))
+        } else if u < p_diffuse + p_specular + p_clearcoat {
+            let wm = sample_gtr1(self.clearcoat_alpha);
+            if wo.dot(wm) <= 0.0 {
+                return None;
+            }
+            reflect_local(wo, wm)
+        } else {
+            let wm = self.distribution.sample_wm(wo);
+            if random_double() < fr_dielectric(wo.dot(wm), self.eta) {
+                reflect_local(wo, wm)
+            } else {
+                refract_local(wo, wm, self.eta)?
+            }
+        };
+        if wi.z() == 0.0 {
+            return None;
+        }
+        Some(wi)
+    }
+}
+
+impl Material for Principled {
+    fn sample(&self, r_in: &Ray<f32>, rec: &HitRecord) -> Option<BsdfSample> {
+        let frame = Onb::new(rec.normal);
+        let wo = frame.to_local(-unit_v!(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let lobes = self.lobes(rec, wo);
+        let wi = lobes.sample(wo)?;
+        let pdf = lobes.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let flags = if wi.z() > 0.0 {
+            LobeFlags::REFLECTION
+        } else {
+            LobeFlags::TRANSMISSION
+        };
+        Some(BsdfSample {
+            wi: frame.to_world(wi),
+            value: lobes.eval(wo, wi),
+            pdf,
+            flags: flags | LobeFlags::DIFFUSE | LobeFlags::GLOSSY,
+        })
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> Vec3<f32> {
+        let frame = Onb::new(rec.normal);
+        let wo = frame.to_local(wo);
+        self.lobes(rec, wo).eval(wo, frame.to_local(wi))
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        let frame = Onb::new(rec.normal);
+        let wo = frame.to_local(wo);
+        self.lobes(rec, wo).pdf(wo, frame.to_local(wi))
+    }
+}
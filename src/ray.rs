@@ -8,6 +8,8 @@ use std::{
 pub struct Ray<F> {
     pub origin: Vec3<F>,
     pub direction: Vec3<F>,
+    // moment within the shutter interval the ray exists at
+    pub time: f32,
 }
 
 impl<
@@ -20,7 +22,19 @@ impl<
     > Ray<F>
 {
     pub fn new(origin: Vec3<F>, direction: Vec3<F>) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(origin: Vec3<F>, direction: Vec3<F>, time: f32) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn origin(&self) -> Vec3<F> {
@@ -31,6 +45,10 @@ impl<
         self.direction
     }
 
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
     pub fn at(&self, t: F) -> Vec3<F> {
         self.origin + self.direction * t
     }
@@ -0,0 +1,69 @@
+use std::cell::Cell;
+
+// pcg32, small and fast with a state that is trivial to save
+// https://www.pcg-random.org/
+#[derive(Debug, Clone, Copy)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    // uniform in [0, 1), 24 bits so it never rounds up to 1
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+}
+
+// splitmix64 finalizer, spreads nearby inputs over the whole range
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+thread_local! {
+    static RNG: Cell<Pcg32> = const {
+        Cell::new(Pcg32 {
+            state: 0x853c49e6748fea9b,
+            inc: 0xda3e39cb94b95bdb,
+        })
+    };
+}
+
+pub fn next_f32() -> f32 {
+    RNG.with(|rng| {
+        let mut r = rng.get();
+        let v = r.next_f32();
+        rng.set(r);
+        v
+    })
+}
+
+// every sample of a render draws from its own sequence, picked by the render seed, the
+// pixel and the sample's index there. images come out the same however the samples are
+// split into passes, tiles or machines
+pub fn seed_sample(seed: u64, pixel: usize, sample: u32) {
+    let rng = Pcg32::new(mix(seed ^ mix(pixel as u64)), sample as u64);
+    RNG.with(|r| r.set(rng));
+}
@@ -0,0 +1,162 @@
+// piecewise constant distributions over [0, 1) and [0, 1)^2, sampled by inverting the cdf
+// https://pbr-book.org/3ed-2018/Monte_Carlo_Integration/Sampling_Random_Variables
+
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f32>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f32;
+        }
+        let integral = cdf[n];
+        if integral == 0.0 {
+            // nothing to importance sample, fall back to uniform
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f32 / n as f32;
+            }
+        } else {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    // returns the sample in [0, 1), its density and the bucket it fell into
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        // last cdf entry <= u
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+        let pdf = self.pdf(offset);
+        ((offset as f32 + du) / self.count() as f32, pdf, offset)
+    }
+
+    pub fn pdf(&self, offset: usize) -> f32 {
+        if self.integral == 0.0 {
+            return 1.0;
+        }
+        self.func[offset].abs() / self.integral
+    }
+}
+
+// func is row major, nv rows of nu values, sampled as a marginal over rows then a row
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f32], nu: usize, nv: usize) -> Self {
+        let conditional: Vec<Distribution1D> = func
+            .chunks_exact(nu)
+            .take(nv)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|c| c.integral()).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    // (u, v) in [0, 1)^2 with the joint density
+    pub fn sample_continuous(&self, u0: f32, u1: f32) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample_continuous(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f32, v: f32) -> f32 {
+        let row = ((v * self.marginal.count() as f32) as usize).min(self.marginal.count() - 1);
+        let conditional = &self.conditional[row];
+        let column = ((u * conditional.count() as f32) as usize).min(conditional.count() - 1);
+        if self.marginal.integral() == 0.0 {
+            return 1.0;
+        }
+        conditional.func[column].abs() / self.marginal.integral()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_follow_the_function() {
+        let d = Distribution1D::new(vec![1.0, 3.0, 0.0, 4.0]);
+        assert_eq!(d.integral(), 2.0);
+        let n = 100_000;
+        let mut counts = [0; 4];
+        for i in 0..n {
+            let (x, pdf, offset) = d.sample_continuous((i as f32 + 0.5) / n as f32);
+            assert_eq!(offset, ((x * 4.0) as usize).min(3));
+            assert_eq!(pdf, d.pdf(offset));
+            counts[offset] += 1;
+        }
+        for (offset, &count) in counts.iter().enumerate() {
+            // bucket probability is pdf times its width
+            let expected = d.pdf(offset) / 4.0;
+            assert!(
+                (count as f32 / n as f32 - expected).abs() < 1e-3,
+                "{offset}"
+            );
+        }
+
+        // nothing to follow, uniform
+        let flat = Distribution1D::new(vec![0.0; 5]);
+        let (x, pdf, _) = flat.sample_continuous(0.3);
+        assert!((x - 0.3).abs() < 1e-6 && pdf == 1.0);
+    }
+
+    #[test]
+    fn joint_density_integrates_to_one() {
+        let (nu, nv) = (4, 3);
+        let func = [
+            1.0, 0.0, 2.0, 5.0, //
+            0.5, 0.5, 0.5, 0.5, //
+            0.0, 0.0, 8.0, 0.0,
+        ];
+        let d = Distribution2D::new(&func, nu, nv);
+        let n = 600;
+        let mut total = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                total += d.pdf((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+            }
+        }
+        assert!((total / (n * n) as f32 - 1.0).abs() < 1e-3, "{total}");
+
+        for i in 0..100 {
+            for j in 0..100 {
+                let ((u, v), pdf) = d.sample_continuous(i as f32 / 100.0, j as f32 / 100.0);
+                assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                assert!((pdf - d.pdf(u, v)).abs() < 1e-4 * pdf, "{u} {v}");
+                assert!(pdf > 0.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::rc::Rc;
+
+use crate::{
+    environment::EnvironmentLight,
+    light::{DirectionalLight, Light, PointLight, QuadLight, SphereLight, SpotLight},
+    material::{DiffuseLight, Material},
+    primitives::Quad,
+    random_double,
+    sky::SunSky,
+    vec3::Vec3,
+    HittableList, Sphere,
+};
+
+macro_rules! f32_len {
+    ($v:expr) => {{
+        let mut i: i32 = $v.to_bits() as i32;
+        i = 0x1fbd3f7d_i32.wrapping_add(i >> 1);
+        let y = f32::from_bits(i as u32);
+        (((y * y) + $v) / (y)) * 0.5
+    }};
+}
+
+macro_rules! unit_v {
+    ($v:expr) => {
+        $v / f32_len!($v.length_squared())
+    };
+}
+
+// what rays that leave the scene see, on top of any lights at infinity
+pub enum Background {
+    // white to light blue going up
+    Gradient,
+    Solid(Vec3<f32>),
+}
+
+impl Background {
+    pub fn value(&self, dir: Vec3<f32>) -> Vec3<f32> {
+        match self {
+            Background::Gradient => {
+                let unit_direction = unit_v!(dir);
+                let a = (unit_direction.y() + 1.0) * 0.5;
+                Vec3::new(1.0, 1.0, 1.0) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.0) * a
+            }
+            Background::Solid(color) => *color,
+        }
+    }
+}
+
+// geometry plus the subset of it that direct lighting samples
+pub struct Scene {
+    pub world: HittableList,
+    pub lights: Vec<Rc<dyn Light>>,
+    pub background: Background,
+}
+
+impl Scene {
+    pub fn new(world: HittableList) -> Self {
+        Self {
+            world,
+            lights: Vec::new(),
+            background: Background::Gradient,
+        }
+    }
+
+    pub fn add_light(&mut self, light: Rc<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    // emissive sphere that is both visible and sampled
+    pub fn add_sphere_light(&mut self, center: Vec3<f32>, radius: f32, emit: Vec3<f32>) {
+        self.world.add(Box::new(Sphere {
+            center,
+            radius,
+            material: Rc::new(Box::new(DiffuseLight { emit })),
+        }));
+        self.add_light(Rc::new(SphereLight {
+            center,
+            radius,
+            emit,
+        }));
+    }
+
+    // emissive parallelogram that is both visible and sampled, emitting towards u x v
+    pub fn add_quad_light(&mut self, q: Vec3<f32>, u: Vec3<f32>, v: Vec3<f32>, emit: Vec3<f32>) {
+        let material: Rc<Box<dyn Material>> = Rc::new(Box::new(DiffuseLight { emit }));
+        self.world
+            .add(Box::new(Quad::new(q, u, v, material.clone())));
+        self.add_light(Rc::new(QuadLight {
+            quad: Quad::new(q, u, v, material),
+            emit,
+        }));
+    }
+
+    pub fn add_point_light(&mut self, position: Vec3<f32>, intensity: Vec3<f32>) {
+        self.add_light(Rc::new(PointLight {
+            position,
+            intensity,
+        }));
+    }
+
+    // cone angles are half angles in degrees
+    pub fn add_spot_light(
+        &mut self,
+        position: Vec3<f32>,
+        target: Vec3<f32>,
+        intensity: Vec3<f32>,
+        falloff_start: f32,
+        total_width: f32,
+    ) {
+        self.add_light(Rc::new(SpotLight::new(
+            position,
+            target,
+            intensity,
+            falloff_start,
+            total_width,
+        )));
+    }
+
+    pub fn add_directional_light(
+        &mut self,
+        to_light: Vec3<f32>,
+        irradiance: Vec3<f32>,
+        angular_diameter: f32,
+    ) {
+        self.add_light(Rc::new(DirectionalLight::new(
+            to_light,
+            irradiance,
+            angular_diameter,
+        )));
+    }
+
+    // image based lighting, replaces the background
+    pub fn set_environment(&mut self, environment: EnvironmentLight) {
+        self.background = Background::Solid(Vec3::new(0.0, 0.0, 0.0));
+        self.add_light(Rc::new(environment));
+    }
+
+    // analytic daylight, replaces the background
+    pub fn set_sun_sky(&mut self, sun_sky: &SunSky) {
+        let (sky, sun) = sun_sky.lights(512);
+        self.set_environment(sky);
+        self.add_light(Rc::new(sun));
+    }
+
+    // pick one light uniformly, returns it with the probability of picking it
+    pub fn pick_light(&self) -> Option<(&Rc<dyn Light>, f32)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let n = self.lights.len();
+        let i = ((random_double() * n as f32) as usize).min(n - 1);
+        Some((&self.lights[i], 1.0 / n as f32))
+    }
+
+    // density of light sampling producing wi from p, over all lights
+    pub fn light_pdf(&self, p: Vec3<f32>, wi: Vec3<f32>) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let total: f32 = self.lights.iter().map(|l| l.pdf_li(p, wi)).sum();
+        total / self.lights.len() as f32
+    }
+
+    // radiance from lights at infinity along dir, without the background
+    pub fn infinite_light(&self, dir: Vec3<f32>) -> Vec3<f32> {
+        self.lights
+            .iter()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |acc, l| acc + l.le(dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn light_pdf_is_the_density_of_picking_then_sampling() {
+        // two overlapping cones seen from the origin
+        let mut scene = Scene::new(HittableList::default());
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        scene.add_sphere_light(Vec3::new(0.0, 0.0, 2.0), 1.0, white);
+        scene.add_sphere_light(Vec3::new(0.8, 0.0, 2.0), 0.7, white);
+        let p = Vec3::new(0.0, 0.0, 0.0);
+
+        // light_pdf integrates to one and the solid angle of the union, by quadrature
+        let n = 1000;
+        let (mut total, mut union) = (0.0, 0.0);
+        for i in 0..n {
+            let z = 2.0 * (i as f32 + 0.5) / n as f32 - 1.0;
+            let r = (1.0 - z * z).sqrt();
+            for j in 0..n {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+                let pdf = scene.light_pdf(p, Vec3::new(r * phi.cos(), r * phi.sin(), z)) as f64;
+                total += pdf;
+                if pdf > 0.0 {
+                    union += 1.0;
+                }
+            }
+        }
+        let cell = 4.0 * std::f64::consts::PI / (n * n) as f64;
+        let (total, union) = (total * cell, union * cell);
+        assert!((total - 1.0).abs() < 1e-2, "{total}");
+
+        // weighting picked samples by it estimates that solid angle, the overlap would be
+        // counted twice if light_pdf only saw the light that was sampled
+        let samples = 200_000;
+        let mut estimate = 0.0;
+        for _ in 0..samples {
+            let (light, _) = scene.pick_light().unwrap();
+            let s = light.sample_li(p).unwrap();
+            estimate += 1.0 / scene.light_pdf(p, s.wi) as f64;
+        }
+        let estimate = estimate / samples as f64;
+        assert!(
+            (estimate - union).abs() < 1e-2 * union,
+            "{estimate} {union}"
+        );
+    }
+}
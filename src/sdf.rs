@@ -0,0 +1,377 @@
+use std::rc::Rc;
+
+use crate::{
+    aabb::Aabb, interval::Interval, material::Material, ray::Ray, vec3::Vec3, HitRecord, Hittable,
+    Sphere,
+};
+
+// signed distance to a surface, negative inside. anything that can underestimate the
+// distance works, estimates that overshoot need a smaller step scale on the object
+pub trait Sdf {
+    fn distance(&self, p: Vec3<f32>) -> f32;
+    // has to contain the whole surface, marching never leaves it
+    fn bounds(&self) -> Aabb;
+}
+
+fn length(v: Vec3<f32>) -> f32 {
+    v.length_squared().sqrt()
+}
+
+fn map(v: Vec3<f32>, f: impl Fn(f32) -> f32) -> Vec3<f32> {
+    Vec3::new(f(v.x()), f(v.y()), f(v.z()))
+}
+
+fn grow(b: Aabb, delta: f32) -> Aabb {
+    Aabb {
+        x: b.x.expand(2.0 * delta),
+        y: b.y.expand(2.0 * delta),
+        z: b.z.expand(2.0 * delta),
+    }
+}
+
+pub struct SdfSphere {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+}
+
+impl SdfSphere {
+    pub fn shared(center: Vec3<f32>, radius: f32) -> Rc<dyn Sdf> {
+        Rc::new(Self { center, radius })
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        length(p - self.center) - self.radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(self.center - r, self.center + r)
+    }
+}
+
+// axis aligned, half_size from the center to each face
+pub struct SdfBox {
+    pub center: Vec3<f32>,
+    pub half_size: Vec3<f32>,
+}
+
+impl SdfBox {
+    pub fn shared(center: Vec3<f32>, half_size: Vec3<f32>) -> Rc<dyn Sdf> {
+        Rc::new(Self { center, half_size })
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        let q = map(p - self.center, f32::abs) - self.half_size;
+        length(map(q, |c| c.max(0.0))) + q.x().max(q.y()).max(q.z()).min(0.0)
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(self.center - self.half_size, self.center + self.half_size)
+    }
+}
+
+// ring in the xz plane around center
+pub struct SdfTorus {
+    pub center: Vec3<f32>,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl SdfTorus {
+    pub fn shared(center: Vec3<f32>, major_radius: f32, minor_radius: f32) -> Rc<dyn Sdf> {
+        Rc::new(Self {
+            center,
+            major_radius,
+            minor_radius,
+        })
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        let p = p - self.center;
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major_radius;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor_radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        let (r, h) = (self.major_radius + self.minor_radius, self.minor_radius);
+        Aabb::from_points(
+            self.center - Vec3::new(r, h, r),
+            self.center + Vec3::new(r, h, r),
+        )
+    }
+}
+
+// inflates the shape, rounding off its edges
+pub struct Rounded {
+    pub inner: Rc<dyn Sdf>,
+    pub radius: f32,
+}
+
+impl Rounded {
+    pub fn shared(inner: Rc<dyn Sdf>, radius: f32) -> Rc<dyn Sdf> {
+        Rc::new(Self { inner, radius })
+    }
+}
+
+impl Sdf for Rounded {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        self.inner.distance(p) - self.radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        grow(self.inner.bounds(), self.radius)
+    }
+}
+
+// polynomial smooth minimum, k is roughly the size of the blend
+// https://iquilezles.org/articles/smin/
+pub struct SmoothUnion {
+    pub a: Rc<dyn Sdf>,
+    pub b: Rc<dyn Sdf>,
+    pub k: f32,
+}
+
+impl SmoothUnion {
+    pub fn shared(a: Rc<dyn Sdf>, b: Rc<dyn Sdf>, k: f32) -> Rc<dyn Sdf> {
+        Rc::new(Self { a, b, k })
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0.0 {
+            return da.min(db);
+        }
+        let h = (self.k - (da - db).abs()).max(0.0) / self.k;
+        da.min(db) - h * h * self.k * 0.25
+    }
+
+    fn bounds(&self) -> Aabb {
+        grow(
+            Aabb::enclosing(&self.a.bounds(), &self.b.bounds()),
+            self.k * 0.25,
+        )
+    }
+}
+
+// copies of the shape every period along each axis, count copies either side of the
+// original. the shape should fit in one cell or neighbours cut into it
+pub struct Repeat {
+    pub inner: Rc<dyn Sdf>,
+    pub period: Vec3<f32>,
+    pub count: [i32; 3],
+}
+
+impl Repeat {
+    pub fn shared(inner: Rc<dyn Sdf>, period: Vec3<f32>, count: [i32; 3]) -> Rc<dyn Sdf> {
+        Rc::new(Self {
+            inner,
+            period,
+            count,
+        })
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        let cell = |i: usize| {
+            if self.period[i] <= 0.0 {
+                return 0.0;
+            }
+            let n = self.count[i] as f32;
+            (p[i] / self.period[i]).round().clamp(-n, n) * self.period[i]
+        };
+        self.inner
+            .distance(p - Vec3::new(cell(0), cell(1), cell(2)))
+    }
+
+    fn bounds(&self) -> Aabb {
+        let extent = Vec3::new(
+            self.period.x() * self.count[0] as f32,
+            self.period.y() * self.count[1] as f32,
+            self.period.z() * self.count[2] as f32,
+        );
+        let b = self.inner.bounds();
+        Aabb::from_points(b.min() - extent, b.max() + extent)
+    }
+}
+
+// sine bumps on the surface. the result isn't a true distance anymore, march it with a
+// step scale around 1 / (1 + amplitude * frequency)
+pub struct Displace {
+    pub inner: Rc<dyn Sdf>,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl Displace {
+    pub fn shared(inner: Rc<dyn Sdf>, amplitude: f32, frequency: f32) -> Rc<dyn Sdf> {
+        Rc::new(Self {
+            inner,
+            amplitude,
+            frequency,
+        })
+    }
+}
+
+impl Sdf for Displace {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        let q = p * self.frequency;
+        self.inner.distance(p) + self.amplitude * q.x().sin() * q.y().sin() * q.z().sin()
+    }
+
+    fn bounds(&self) -> Aabb {
+        grow(self.inner.bounds(), self.amplitude.abs())
+    }
+}
+
+// distance estimate of the power 8 mandelbulb, scaled around center
+// https://iquilezles.org/articles/mandelbulb/
+pub struct Mandelbulb {
+    pub center: Vec3<f32>,
+    pub scale: f32,
+    pub power: f32,
+    pub iterations: usize,
+}
+
+impl Mandelbulb {
+    pub fn shared(center: Vec3<f32>, scale: f32) -> Rc<dyn Sdf> {
+        Rc::new(Self {
+            center,
+            scale,
+            power: 8.0,
+            iterations: 12,
+        })
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Vec3<f32>) -> f32 {
+        let c = (p - self.center) / self.scale;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = length(z);
+        for _ in 0..self.iterations {
+            if r > 2.0 {
+                break;
+            }
+            let theta = (z.z() / r).clamp(-1.0, 1.0).acos() * self.power;
+            let phi = z.y().atan2(z.x()) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ) * zr
+                + c;
+            r = length(z);
+        }
+        0.5 * r.ln() * r / dr * self.scale
+    }
+
+    fn bounds(&self) -> Aabb {
+        let r = Vec3::new(1.2, 1.2, 1.2) * self.scale;
+        Aabb::from_points(self.center - r, self.center + r)
+    }
+}
+
+// sphere traces a distance field inside its bounds
+pub struct SdfObject {
+    pub sdf: Rc<dyn Sdf>,
+    pub material: Rc<Box<dyn Material>>,
+    // fraction of the distance taken each step, below 1 for estimates that overshoot
+    pub step_scale: f32,
+    pub max_steps: usize,
+    // how close counts as on the surface
+    pub epsilon: f32,
+    bbox: Aabb,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Rc<dyn Sdf>, material: Rc<Box<dyn Material>>) -> Self {
+        let bbox = grow(sdf.bounds(), 1e-3);
+        Self {
+            sdf,
+            material,
+            step_scale: 1.0,
+            max_steps: 256,
+            epsilon: 1e-4,
+            bbox,
+        }
+    }
+
+    // central differences on a tetrahedron, four lookups instead of six
+    fn normal(&self, p: Vec3<f32>) -> Vec3<f32> {
+        let h = self.epsilon * 2.0;
+        let k = |x: f32, y: f32, z: f32| {
+            let d = Vec3::new(x, y, z);
+            d * self.sdf.distance(p + d * h)
+        };
+        let n = k(1.0, -1.0, -1.0) + k(-1.0, -1.0, 1.0) + k(-1.0, 1.0, -1.0) + k(1.0, 1.0, 1.0);
+        n / length(n).max(1e-12)
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(
+        &self,
+        r: &Ray<f32>,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        material: Rc<Box<dyn Material>>,
+    ) -> bool {
+        let Some(span) = self.bbox.hit(r, ray_t) else {
+            return false;
+        };
+        let dir_len = length(r.direction());
+        // rays leaving a refractive surface start inside, march the unsigned distance
+        let mut t = span.min;
+        let mut found = false;
+        for _ in 0..self.max_steps {
+            let d = self.sdf.distance(r.at(t)).abs();
+            if d < self.epsilon * t.max(1.0) {
+                found = true;
+                break;
+            }
+            t += d * self.step_scale / dir_len;
+            if t >= span.max {
+                break;
+            }
+        }
+        if !found || !ray_t.surrounds(t) {
+            return false;
+        }
+
+        let p = r.at(t);
+        let outward_normal = self.normal(p);
+        let (u, v) = Sphere::get_sphere_uv(outward_normal);
+        *rec = HitRecord {
+            p,
+            normal: outward_normal,
+            t,
+            u,
+            v,
+            front_face: false,
+            material: Some(material),
+            color: None,
+        };
+        rec.set_face_normal(r, outward_normal);
+        true
+    }
+
+    fn material(&self) -> Rc<Box<dyn Material>> {
+        self.material.clone()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
@@ -0,0 +1,170 @@
+use std::f32::consts::PI;
+
+use crate::{environment::EnvironmentLight, hdr::HdrImage, light::DirectionalLight, vec3::Vec3};
+
+// preetham, shirley, smits - a practical analytic model for daylight
+// https://courses.cs.duke.edu/cps124/spring08/assign/07_papers/p91-preetham.pdf
+// radiance comes out in kcd/m^2 and gets scaled by intensity
+pub struct SunSky {
+    // unit direction towards the sun
+    pub sun_direction: Vec3<f32>,
+    pub turbidity: f32,
+    pub intensity: f32,
+    // radiance of everything below the horizon relative to the horizon itself
+    pub ground_albedo: f32,
+    theta_sun: f32,
+    zenith: [f32; 3],
+    perez: [[f32; 5]; 3],
+}
+
+// (1 + A e^(B / cos theta)) (1 + C e^(D gamma) + E cos^2 gamma)
+fn perez(coeffs: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coeffs;
+    (1.0 + a * (b / cos_theta.max(0.01)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+fn xyy_to_linear_srgb(x: f32, y: f32, lum: f32) -> Vec3<f32> {
+    if y <= 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    let cx = x / y * lum;
+    let cz = (1.0 - x - y) / y * lum;
+    let cy = lum;
+    Vec3::new(
+        (3.2406 * cx - 1.5372 * cy - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * cy + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * cy + 1.0570 * cz).max(0.0),
+    )
+}
+
+impl SunSky {
+    // elevation above the horizon and azimuth clockwise from -z towards +x, both in degrees
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32, intensity: f32) -> Self {
+        let (e, a) = (elevation.to_radians(), azimuth.to_radians());
+        let sun_direction = Vec3::new(e.cos() * a.sin(), e.sin(), -e.cos() * a.cos());
+        let t = turbidity;
+        let theta_sun = PI * 0.5 - e.clamp(0.0, PI * 0.5);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f32; 4]; 3]| {
+            let th = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+            let row = |r: [f32; 4]| r.iter().zip(th).map(|(c, x)| c * x).sum::<f32>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_yc = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        Self {
+            sun_direction,
+            turbidity,
+            intensity,
+            ground_albedo: 0.3,
+            theta_sun,
+            zenith: [zenith_y.max(0.0), zenith_x, zenith_yc],
+            perez,
+        }
+    }
+
+    // sky radiance, without the sun disk
+    pub fn radiance(&self, dir: Vec3<f32>) -> Vec3<f32> {
+        let dir = dir / dir.length_squared().sqrt();
+        let below = dir.y() < 0.0;
+        // mirror the lower hemisphere onto the horizon
+        let cos_theta = dir.y().abs().max(0.001);
+        let gamma = dir.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let channel = |i: usize| {
+            self.zenith[i] * perez(&self.perez[i], cos_theta, gamma)
+                / perez(&self.perez[i], 1.0, self.theta_sun)
+        };
+        let color = xyy_to_linear_srgb(channel(1), channel(2), channel(0)) * self.intensity;
+        if below {
+            color * self.ground_albedo
+        } else {
+            color
+        }
+    }
+
+    // extraterrestrial illuminance dimmed by rayleigh and aerosol extinction along the
+    // optical path, at roughly 650nm, 550nm and 450nm
+    pub fn sun_irradiance(&self) -> Vec3<f32> {
+        if self.sun_direction.y() <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let theta_deg = self.theta_sun.to_degrees();
+        let air_mass = 1.0 / (self.theta_sun.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+        // angstrom turbidity coefficient
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |lambda_um: f32| {
+            let rayleigh = (-0.008735 * lambda_um.powf(-4.08) * air_mass).exp();
+            let aerosol = (-beta * lambda_um.powf(-1.3) * air_mass).exp();
+            rayleigh * aerosol
+        };
+        Vec3::new(
+            transmittance(0.65),
+            transmittance(0.55),
+            transmittance(0.45),
+        ) * (128.0 * self.intensity)
+    }
+
+    // the sky baked into an importance sampled map plus the sun as a small disk
+    pub fn lights(&self, width: usize) -> (EnvironmentLight, DirectionalLight) {
+        let height = (width / 2).max(1);
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let theta = PI * (y as f32 + 0.5) / height as f32;
+            for x in 0..width {
+                let phi = (((x as f32 + 0.5) / width as f32) - 0.5) * 2.0 * PI;
+                let dir = Vec3::new(
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    -theta.sin() * phi.cos(),
+                );
+                data.push(self.radiance(dir));
+            }
+        }
+        let image = HdrImage {
+            width,
+            height,
+            data,
+        };
+        (
+            EnvironmentLight::new(image, 0.0, 1.0),
+            DirectionalLight::new(self.sun_direction, self.sun_irradiance(), 0.53),
+        )
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    time::Duration,
+};
+
+/// What a render did, summed over everything that rendered it.
+///
+/// Shown as a readable summary by its `Display` impl, [`RenderStats::to_json`] gives the
+/// same as json.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderStats {
+    /// Rays leaving the camera.
+    pub primary_rays: u64,
+    /// Rays continuing a path after a bounce.
+    pub secondary_rays: u64,
+    /// Rays towards a light, testing whether it is visible.
+    pub shadow_rays: u64,
+    /// Ray tests against whole objects of the scene and against the primitives in them.
+    pub intersection_tests: u64,
+    /// Nodes visited in the hierarchies of meshes.
+    pub bvh_node_visits: u64,
+    /// Paths by the number of surfaces they hit, the last entry includes the paths cut off
+    /// at the maximum depth.
+    pub path_lengths: Vec<u64>,
+    /// Wall clock time of this session, not counting earlier ones a checkpoint resumed.
+    pub wall_time: Duration,
+}
+
+impl RenderStats {
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.secondary_rays + self.shadow_rays
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        self.rays() as f64 / self.wall_time.as_secs_f64().max(1e-9)
+    }
+
+    /// Adds the counts of `other`. Wall times overlap when renders run side by side, so
+    /// the longer one is kept.
+    pub fn merge(&mut self, other: &RenderStats) {
+        self.primary_rays += other.primary_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.intersection_tests += other.intersection_tests;
+        self.bvh_node_visits += other.bvh_node_visits;
+        if self.path_lengths.len() < other.path_lengths.len() {
+            self.path_lengths.resize(other.path_lengths.len(), 0);
+        }
+        for (a, b) in self.path_lengths.iter_mut().zip(&other.path_lengths) {
+            *a += b;
+        }
+        self.wall_time = self.wall_time.max(other.wall_time);
+    }
+
+    pub fn to_json(&self) -> String {
+        let lengths: Vec<String> = self.path_lengths.iter().map(|n| n.to_string()).collect();
+        format!(
+            "{{\"wall_time\": {}, \"rays\": {}, \"rays_per_second\": {:.0}, \
+             \"primary_rays\": {}, \"secondary_rays\": {}, \"shadow_rays\": {}, \
+             \"intersection_tests\": {}, \"bvh_node_visits\": {}, \"path_lengths\": [{}]}}",
+            self.wall_time.as_secs_f64(),
+            self.rays(),
+            self.rays_per_second(),
+            self.primary_rays,
+            self.secondary_rays,
+            self.shadow_rays,
+            self.intersection_tests,
+            self.bvh_node_visits,
+            lengths.join(", ")
+        )
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "wall time           {:.2?}", self.wall_time)?;
+        writeln!(
+            f,
+            "rays                {} ({:.2} M/s)",
+            self.rays(),
+            self.rays_per_second() / 1e6
+        )?;
+        writeln!(f, "  primary           {}", self.primary_rays)?;
+        writeln!(f, "  secondary         {}", self.secondary_rays)?;
+        writeln!(f, "  shadow            {}", self.shadow_rays)?;
+        writeln!(f, "intersection tests  {}", self.intersection_tests)?;
+        writeln!(f, "bvh node visits     {}", self.bvh_node_visits)?;
+        write!(f, "path lengths")?;
+        let paths = self.path_lengths.iter().sum::<u64>().max(1) as f64;
+        for (length, &n) in self.path_lengths.iter().enumerate() {
+            if n > 0 {
+                write!(
+                    f,
+                    "\n  {length:>3} {:>16} {:>6.2}%",
+                    n,
+                    100.0 * n as f64 / paths
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// counters of the current thread, cells are cheap enough for the hot loops
+pub(crate) enum Counter {
+    PrimaryRays,
+    SecondaryRays,
+    ShadowRays,
+    IntersectionTests,
+    BvhNodeVisits,
+}
+
+thread_local! {
+    static COUNTERS: [Cell<u64>; 5] = const {
+        [Cell::new(0), Cell::new(0), Cell::new(0), Cell::new(0), Cell::new(0)]
+    };
+    static PATH_LENGTHS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) fn count(counter: Counter, n: u64) {
+    COUNTERS.with(|c| {
+        let c = &c[counter as usize];
+        c.set(c.get() + n);
+    });
+}
+
+fn add_paths(length: usize, n: u64) {
+    PATH_LENGTHS.with(|p| {
+        let mut p = p.borrow_mut();
+        if p.len() <= length {
+            p.resize(length + 1, 0);
+        }
+        p[length] += n;
+    });
+}
+
+pub(crate) fn record_path(length: usize) {
+    add_paths(length, 1);
+}
+
+// the counts since the last take, zeroing them
+pub(crate) fn take() -> RenderStats {
+    let c = COUNTERS.with(|c| c.each_ref().map(|c| c.take()));
+    RenderStats {
+        primary_rays: c[0],
+        secondary_rays: c[1],
+        shadow_rays: c[2],
+        intersection_tests: c[3],
+        bvh_node_visits: c[4],
+        path_lengths: PATH_LENGTHS.with(|p| p.take()),
+        wall_time: Duration::ZERO,
+    }
+}
+
+// rays counted since the last take, leaving the counters alone
+pub(crate) fn pending_rays() -> u64 {
+    COUNTERS.with(|c| c[0].get() + c[1].get() + c[2].get())
+}
+
+// counts from somewhere else, like a worker process, for the next take
+pub(crate) fn add(stats: &RenderStats) {
+    let counts = [
+        stats.primary_rays,
+        stats.secondary_rays,
+        stats.shadow_rays,
+        stats.intersection_tests,
+        stats.bvh_node_visits,
+    ];
+    COUNTERS.with(|c| {
+        for (c, n) in c.iter().zip(counts) {
+            c.set(c.get() + n);
+        }
+    });
+    for (length, &n) in stats.path_lengths.iter().enumerate() {
+        add_paths(length, n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(rays: u64, path_lengths: Vec<u64>, secs: u64) -> RenderStats {
+        RenderStats {
+            primary_rays: rays,
+            secondary_rays: 2 * rays,
+            shadow_rays: 3 * rays,
+            intersection_tests: 4 * rays,
+            bvh_node_visits: 5 * rays,
+            path_lengths,
+            wall_time: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn merge_adds_counts_and_keeps_the_longer_time() {
+        let mut a = stats(1, vec![1, 2], 3);
+        a.merge(&stats(10, vec![0, 1, 5], 2));
+        assert_eq!(a, stats(11, vec![1, 3, 5], 3));
+        a.merge(&RenderStats::default());
+        assert_eq!(a, stats(11, vec![1, 3, 5], 3));
+    }
+
+    #[test]
+    fn json_report() {
+        let json = stats(2, vec![0, 4], 4).to_json();
+        assert_eq!(
+            json,
+            "{\"wall_time\": 4, \"rays\": 12, \"rays_per_second\": 3, \"primary_rays\": 2, \
+             \"secondary_rays\": 4, \"shadow_rays\": 6, \"intersection_tests\": 8, \
+             \"bvh_node_visits\": 10, \"path_lengths\": [0, 4]}"
+        );
+        let parsed = crate::json::Json::parse(&json).unwrap();
+        assert_eq!(parsed["path_lengths"].as_array().len(), 2);
+        assert_eq!(parsed["rays"].as_usize(), Some(12));
+    }
+
+    #[test]
+    fn counters_round_trip_through_take() {
+        take();
+        count(Counter::PrimaryRays, 2);
+        count(Counter::ShadowRays, 1);
+        record_path(3);
+        assert_eq!(pending_rays(), 3);
+        add(&stats(1, vec![1], 0));
+        let taken = take();
+        assert_eq!((taken.primary_rays, taken.shadow_rays), (3, 4));
+        assert_eq!(taken.path_lengths, vec![1, 0, 0, 1]);
+        assert_eq!(take(), RenderStats::default());
+    }
+}
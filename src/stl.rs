@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use crate::{mesh::MeshData, vec3::Vec3};
+
+// stereolithography files, ascii or binary. stl repeats every corner for each facet, they
+// are welded back together by exact position so the bvh and memory stay small. facet
+// normals are ignored, the mesh is shaded flat from its winding
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<MeshData> {
+    read(&fs::read(path)?)
+}
+
+// binary files may also start with "solid", so the size decides first
+pub fn read(bytes: &[u8]) -> Result<MeshData> {
+    if bytes.len() >= 84 {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        if bytes.len() == 84 + count * 50 {
+            return read_binary(&bytes[84..], count);
+        }
+    }
+    if bytes.starts_with(b"solid") {
+        return read_ascii(bytes);
+    }
+    Err(invalid("not an stl file"))
+}
+
+#[derive(Default)]
+struct Welder {
+    mesh: MeshData,
+    seen: HashMap<[u32; 3], usize>,
+}
+
+impl Welder {
+    fn vertex(&mut self, p: Vec3<f32>) -> usize {
+        let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+        let positions = &mut self.mesh.positions;
+        *self.seen.entry(key).or_insert_with(|| {
+            positions.push(p);
+            positions.len() - 1
+        })
+    }
+
+    fn triangle(&mut self, corners: [Vec3<f32>; 3]) {
+        let tri = corners.map(|p| self.vertex(p));
+        // zero area facets are common in exports and only cost time
+        if tri[0] != tri[1] && tri[1] != tri[2] && tri[0] != tri[2] {
+            self.mesh.triangles.push(tri);
+        }
+    }
+
+    fn finish(self) -> Result<MeshData> {
+        if self.mesh.triangles.is_empty() {
+            return Err(invalid("stl has no triangles"));
+        }
+        Ok(self.mesh)
+    }
+}
+
+// 80 byte header, count, then 50 bytes per facet: normal, three corners, attribute bytes
+fn read_binary(data: &[u8], count: usize) -> Result<MeshData> {
+    let mut welder = Welder::default();
+    for facet in data.chunks_exact(50).take(count) {
+        let f = |i: usize| {
+            let b = &facet[12 + i * 4..16 + i * 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        };
+        welder.triangle([
+            Vec3::new(f(0), f(1), f(2)),
+            Vec3::new(f(3), f(4), f(5)),
+            Vec3::new(f(6), f(7), f(8)),
+        ]);
+    }
+    welder.finish()
+}
+
+fn read_ascii(bytes: &[u8]) -> Result<MeshData> {
+    let text = std::str::from_utf8(bytes).map_err(|_| invalid("bad ascii stl"))?;
+    let mut welder = Welder::default();
+    let mut corners = Vec::with_capacity(3);
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("vertex") => {
+                let v: Vec<f32> = words
+                    .map(|w| w.parse::<f32>())
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|_| invalid("bad stl vertex"))?;
+                if v.len() != 3 {
+                    return Err(invalid("bad stl vertex"));
+                }
+                corners.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            Some("endloop") => {
+                // polygons beyond triangles aren't valid stl but fan them anyway
+                for k in 1..corners.len().saturating_sub(1) {
+                    welder.triangle([corners[0], corners[k], corners[k + 1]]);
+                }
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    welder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two facets of a square sharing a diagonal, and a degenerate one
+    const SQUARE: [[[f32; 3]; 3]; 3] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 0.0]],
+    ];
+
+    fn check_square(mesh: MeshData) {
+        assert_eq!(mesh.positions.len(), 4);
+        let p = mesh.positions[3];
+        assert_eq!([p.x(), p.y(), p.z()], [0.0, 1.0, 0.0]);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn ascii_welds_corners() {
+        let mut stl = String::from("solid square\n");
+        for facet in SQUARE {
+            stl += "  facet normal 0 0 1\n    outer loop\n";
+            for [x, y, z] in facet {
+                stl += &format!("      vertex {x} {y} {z}\n");
+            }
+            stl += "    endloop\n  endfacet\n";
+        }
+        stl += "endsolid square\n";
+        check_square(read(stl.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn binary_welds_corners() {
+        // the header starting with solid must not make it ascii
+        let mut stl = b"solid but binary".to_vec();
+        stl.resize(80, 0);
+        stl.extend((SQUARE.len() as u32).to_le_bytes());
+        for facet in SQUARE {
+            let normal = [0.0, 0.0, 1.0];
+            for v in normal.into_iter().chain(facet.into_iter().flatten()) {
+                stl.extend(f32::to_le_bytes(v));
+            }
+            stl.extend([0, 0]);
+        }
+        check_square(read(&stl).unwrap());
+    }
+}
@@ -0,0 +1,142 @@
+use std::rc::Rc;
+
+use crate::{hdr::HdrImage, vec3::Vec3, HitRecord};
+
+pub trait Texture {
+    fn value(&self, rec: &HitRecord) -> Vec3<f32>;
+}
+
+pub struct SolidColor {
+    pub albedo: Vec3<f32>,
+}
+
+impl SolidColor {
+    pub fn shared(albedo: Vec3<f32>) -> Rc<dyn Texture> {
+        Rc::new(Self { albedo })
+    }
+
+    // scalar parameters read the first channel
+    pub fn scalar(value: f32) -> Rc<dyn Texture> {
+        Self::shared(Vec3::new(value, value, value))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _rec: &HitRecord) -> Vec3<f32> {
+        self.albedo
+    }
+}
+
+// solid 3d checker, alternates every `scale` world units
+pub struct Checker {
+    pub inv_scale: f32,
+    pub even: Rc<dyn Texture>,
+    pub odd: Rc<dyn Texture>,
+}
+
+impl Checker {
+    pub fn shared(scale: f32, even: Rc<dyn Texture>, odd: Rc<dyn Texture>) -> Rc<dyn Texture> {
+        Rc::new(Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        })
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, rec: &HitRecord) -> Vec3<f32> {
+        let x = (self.inv_scale * rec.p.x()).floor() as i32;
+        let y = (self.inv_scale * rec.p.y()).floor() as i32;
+        let z = (self.inv_scale * rec.p.z()).floor() as i32;
+        if (x + y + z) % 2 == 0 {
+            self.even.value(rec)
+        } else {
+            self.odd.value(rec)
+        }
+    }
+}
+
+// interpolated mesh vertex colors, fallback where the hit has none
+pub struct VertexColor {
+    pub fallback: Rc<dyn Texture>,
+}
+
+impl VertexColor {
+    pub fn shared(fallback: Rc<dyn Texture>) -> Rc<dyn Texture> {
+        Rc::new(Self { fallback })
+    }
+}
+
+impl Texture for VertexColor {
+    fn value(&self, rec: &HitRecord) -> Vec3<f32> {
+        rec.color.unwrap_or_else(|| self.fallback.value(rec))
+    }
+}
+
+// bilinearly filtered image, repeating outside [0, 1]. v = 0 is the bottom row
+pub struct ImageTexture {
+    pub image: Rc<HdrImage>,
+}
+
+impl ImageTexture {
+    pub fn shared(image: Rc<HdrImage>) -> Rc<dyn Texture> {
+        Rc::new(Self { image })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, rec: &HitRecord) -> Vec3<f32> {
+        let (w, h) = (self.image.width, self.image.height);
+        if w == 0 || h == 0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let x = rec.u.rem_euclid(1.0) * w as f32 - 0.5;
+        let y = (1.0 - rec.v).rem_euclid(1.0) * h as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let wrap = |i: f32, n: usize| (i as i64).rem_euclid(n as i64) as usize;
+        let (x0, x1) = (wrap(x0, w), wrap(x0 + 1.0, w));
+        let (y0, y1) = (wrap(y0, h), wrap(y0 + 1.0, h));
+        let top = self.image.get(x0, y0) * (1.0 - fx) + self.image.get(x1, y0) * fx;
+        let bottom = self.image.get(x0, y1) * (1.0 - fx) + self.image.get(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+// componentwise a * b, for tinting a texture with a factor
+pub struct Product {
+    pub a: Rc<dyn Texture>,
+    pub b: Rc<dyn Texture>,
+}
+
+impl Product {
+    pub fn shared(a: Rc<dyn Texture>, b: Rc<dyn Texture>) -> Rc<dyn Texture> {
+        Rc::new(Self { a, b })
+    }
+}
+
+impl Texture for Product {
+    fn value(&self, rec: &HitRecord) -> Vec3<f32> {
+        self.a.value(rec) * self.b.value(rec)
+    }
+}
+
+// one channel of another texture in all three, for scalars packed into an rgb image
+pub struct Channel {
+    pub inner: Rc<dyn Texture>,
+    pub channel: usize,
+}
+
+impl Channel {
+    pub fn shared(inner: Rc<dyn Texture>, channel: usize) -> Rc<dyn Texture> {
+        Rc::new(Self { inner, channel })
+    }
+}
+
+impl Texture for Channel {
+    fn value(&self, rec: &HitRecord) -> Vec3<f32> {
+        let c = self.inner.value(rec)[self.channel];
+        Vec3::new(c, c, c)
+    }
+}
@@ -0,0 +1,169 @@
+use std::f32::consts::PI;
+
+/// A rectangle of pixels, `x` and `y` are its top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The part of `self` inside `other`, empty if they don't overlap.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x0 = self.x.max(other.x);
+        let y0 = self.y.max(other.y);
+        let x1 = (self.x + self.width).min(other.x + other.width);
+        let y1 = (self.y + self.height).min(other.y + other.height);
+        Rect::new(x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0))
+    }
+
+    pub fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Pixel coordinates row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |py| (x..x + width).map(move |px| (px, py)))
+    }
+}
+
+/// Order tiles are rendered in. The image is the same either way, only the order pixels
+/// fill in differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// Left to right, top to bottom.
+    Scanline,
+    /// Outwards from the center, where the subject usually is.
+    Spiral,
+    /// Along a hilbert curve, neighbouring tiles follow each other and touch much the
+    /// same parts of the scene.
+    #[default]
+    Hilbert,
+}
+
+// distance along a hilbert curve filling an n by n grid, n a power of two
+fn hilbert_index(n: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+        // rotate the quadrant so the curve joins up
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+/// Splits `window` into tiles of at most `size` by `size` pixels, in `order`.
+pub fn tiles(window: Rect, size: usize, order: TileOrder) -> Vec<Rect> {
+    let size = size.max(1);
+    let nx = window.width.div_ceil(size);
+    let ny = window.height.div_ceil(size);
+    let mut grid: Vec<(usize, usize)> = (0..ny)
+        .flat_map(|ty| (0..nx).map(move |tx| (tx, ty)))
+        .collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            // ring by ring around the center tile, each ring in order of angle
+            let (cx, cy) = ((nx as f32 - 1.0) * 0.5, (ny as f32 - 1.0) * 0.5);
+            let key = |&(tx, ty): &(usize, usize)| {
+                let (dx, dy) = (tx as f32 - cx, ty as f32 - cy);
+                let ring = dx.abs().max(dy.abs()).round() as usize;
+                (ring, dy.atan2(dx).rem_euclid(2.0 * PI))
+            };
+            grid.sort_by(|a, b| {
+                let (ka, kb) = (key(a), key(b));
+                ka.0.cmp(&kb.0).then(ka.1.total_cmp(&kb.1))
+            });
+        }
+        TileOrder::Hilbert => {
+            let n = nx.max(ny).next_power_of_two();
+            grid.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+        }
+    }
+    grid.into_iter()
+        .map(|(tx, ty)| {
+            let (x, y) = (window.x + tx * size, window.y + ty * size);
+            Rect::new(x, y, size, size).intersect(&window)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_order_covers_the_window_once() {
+        let windows = [
+            Rect::new(0, 0, 100, 60),
+            Rect::new(7, 3, 33, 1),
+            Rect::new(5, 9, 1, 1),
+            Rect::new(2, 2, 0, 4),
+        ];
+        for window in windows {
+            for size in [1, 8, 16, 64] {
+                for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+                    let mut seen = vec![0; (window.x + window.width) * (window.y + window.height)];
+                    for tile in tiles(window, size, order) {
+                        assert!(tile.width <= size && tile.height <= size);
+                        assert_eq!(tile.intersect(&window), tile);
+                        for (x, y) in tile.pixels() {
+                            seen[y * (window.x + window.width) + x] += 1;
+                        }
+                    }
+                    let covered: usize = seen.iter().sum();
+                    assert_eq!(covered, window.area(), "{window:?} {size} {order:?}");
+                    assert!(seen.iter().all(|&n| n <= 1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_steps_to_a_neighbour() {
+        for n in [1, 2, 4, 8, 16] {
+            let mut cells: Vec<(usize, usize)> =
+                (0..n).flat_map(|y| (0..n).map(move |x| (x, y))).collect();
+            cells.sort_by_key(|&(x, y)| hilbert_index(n, x, y));
+            let indices: Vec<usize> = cells.iter().map(|&(x, y)| hilbert_index(n, x, y)).collect();
+            assert_eq!(indices, (0..n * n).collect::<Vec<_>>());
+            for pair in cells.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn spiral_starts_in_the_middle() {
+        let first = tiles(Rect::new(0, 0, 50, 50), 10, TileOrder::Spiral)[0];
+        assert_eq!(first, Rect::new(20, 20, 10, 10));
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub},
 };
 
-use crate::random_double_lim;
+use crate::{random_double, random_double_lim};
 
 #[derive(Default, Debug, Clone, Copy)]
 pub struct Vec3<F>([F; 3]);
@@ -25,12 +25,6 @@ macro_rules! unit_v {
     };
 }
 
-macro_rules! random_vec3 {
-    () => {
-        Vec3::<f32>::new(random_double(), random_double(), random_double())
-    };
-}
-
 macro_rules! random_vec3_lim {
     ($min:expr, $max:expr) => {
         Vec3::<f32>::new(
@@ -61,6 +55,16 @@ pub fn random_unit_vec() -> Vec3<f32> {
     unit_v!(random_in_unit_sphere())
 }
 
+// cosine weighted direction around +z
+#[inline(always)]
+pub fn random_cosine_direction() -> Vec3<f32> {
+    let r1 = random_double();
+    let r2 = random_double();
+    let phi = 2.0 * std::f32::consts::PI * r1;
+    let r = r2.sqrt();
+    Vec3::new(phi.cos() * r, phi.sin() * r, (1.0 - r2).sqrt())
+}
+
 #[inline(always)]
 pub fn random_on_hemisphere(normal: Vec3<f32>) -> Vec3<f32> {
     let on_unit_sphere = random_unit_vec();
@@ -118,13 +122,12 @@ impl<
             self.x() * other.y() - self.y() * other.x(),
         )
     }
+}
 
-    // https://suraj.sh/fast-square-root-approximation
-    // magic number might only work for f32
-    // have to wait for this https://github.com/rust-lang/rfcs/blob/master/text/1210-impl-specialization.md
+impl Vec3<f32> {
     #[inline(always)]
-    pub fn length(self) -> F {
-        unimplemented!()
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
     }
 
     #[inline(always)]
@@ -133,20 +136,6 @@ impl<
     }
 }
 
-/*
-impl Vec3<f32> {
-    #[inline(always)]
-    pub fn length(&self) -> f32 {
-        unimplemented!()
-        /*
-        let a = self.length_squared();
-        let x: f64 = <f64 as Into<F>>::into(0x1fbd3f7d as f64) + (a as f64 >> 1);
-        (((x * x) + a) / x) * <f64 as Into<F>>::into(0.5)
-        */
-    }
-}
-*/
-
 impl<
         F: std::marker::Copy
             + Add<Output = F>
@@ -167,13 +156,14 @@ impl<
             + Sub<Output = F>
             + Mul<Output = F>
             + Div<Output = F>
+            + Neg<Output = F>
             + Display,
     > Neg for Vec3<F>
 {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
-        Self::new(self.x(), self.y(), self.z())
+        Self::new(-self.x(), -self.y(), -self.z())
     }
 }
 